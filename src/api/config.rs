@@ -0,0 +1,313 @@
+use super::OPTIONS;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const SEP: &str = "\t";
+const ENV_PREFIX: &str = "SAD_";
+// user config path can be set by SAD_CONFIG variable
+const CONFIG_ENV: &str = "SAD_CONFIG";
+// project local config file looked up in current directory
+pub const PROJECT_CONFIG: &str = "sad.toml";
+
+// Source is where option value comes from, in order of precedence
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    Default,
+    UserConfig(PathBuf),
+    ProjectConfig(PathBuf),
+    Env(String),
+    CommandLine,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::UserConfig(path) | Source::ProjectConfig(path) => {
+                write!(f, "{}", path.display())
+            }
+            Source::Env(name) => write!(f, "env {}", name),
+            Source::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    // long option name
+    pub name: String,
+    pub value: String,
+    pub source: Source,
+}
+
+// Config collects option values of all sources, options of a source
+// with higher precedence replace all values of the option from lower ones
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    entries: Vec<Entry>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        let mut config = Config { entries: vec![] };
+        for (name, value) in [
+            ("format", "tsv"),
+            ("join-kind", "left"),
+            ("key", "Project"),
+            ("on-conflict", "first"),
+        ] {
+            config.add(name, value, Source::Default);
+        }
+        config
+    }
+}
+
+impl Config {
+    // load reads defaults from user config, project config in current
+    // directory and SAD_* environment variables
+    pub fn load() -> Result<Config, String> {
+        let mut config = Config::default();
+        if let Some(path) = user_config_path() {
+            config.read_file(&path, Source::UserConfig(path.clone()))?;
+        }
+        let path = PathBuf::from(PROJECT_CONFIG);
+        config.read_file(&path, Source::ProjectConfig(path.clone()))?;
+        config.add_env(env::vars())?;
+        Ok(config)
+    }
+
+    // add appends option value of source
+    pub fn add(&mut self, name: &str, value: &str, source: Source) {
+        self.entries.push(Entry {
+            name: name.to_string(),
+            value: value.to_string(),
+            source,
+        });
+    }
+
+    // read_file adds options of config file if it exists
+    fn read_file(&mut self, path: &Path, source: Source) -> Result<(), String> {
+        match fs::read_to_string(path) {
+            Ok(s) => self
+                .add_toml(&s, source)
+                .map_err(|err| format!("{}: {}", path.display(), err)),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(format!("{}: {}", path.display(), err)),
+        }
+    }
+
+    // add_toml adds options of toml config, keys are long option names,
+    // values are strings, booleans for flags, numbers or arrays of them
+    pub fn add_toml(&mut self, s: &str, source: Source) -> Result<(), String> {
+        let table: toml::Table = s.parse().map_err(|err: toml::de::Error| err.to_string())?;
+        for (key, value) in table {
+            let key = key.replace('_', "-");
+            let values = match value {
+                toml::Value::Array(items) => items
+                    .into_iter()
+                    .map(|v| scalar(&key, v))
+                    .collect::<Result<Vec<String>, String>>()?,
+                v => vec![scalar(&key, v)?],
+            };
+            check_option(&key, &values)?;
+            for v in values {
+                self.add(&key, &v, source.clone());
+            }
+        }
+        Ok(())
+    }
+
+    // add_env adds options of SAD_<OPTION> variables,
+    // e.g. SAD_SORT_BY sets --sort-by, variables of unknown options
+    // are ignored as they may belong to other programs
+    pub fn add_env<I: IntoIterator<Item = (String, String)>>(
+        &mut self,
+        vars: I,
+    ) -> Result<(), String> {
+        let mut vars: Vec<(String, String)> = vars
+            .into_iter()
+            .filter(|(k, _)| k.starts_with(ENV_PREFIX) && k != CONFIG_ENV)
+            .collect();
+        vars.sort();
+        for (k, v) in vars {
+            let name = k[ENV_PREFIX.len()..].to_lowercase().replace('_', "-");
+            if !OPTIONS.iter().any(|o| o.0 == name && o.0 != "help") {
+                continue;
+            }
+            let values = vec![v];
+            check_option(&name, &values).map_err(|err| format!("env {}: {}", k, err))?;
+            self.add(&name, &values[0], Source::Env(k.clone()));
+        }
+        Ok(())
+    }
+
+    // entries returns values of options from source with the highest
+    // precedence in order they were added
+    pub fn entries(&self) -> Vec<&Entry> {
+        self.entries
+            .iter()
+            .filter(|e| {
+                self.entries
+                    .iter()
+                    .filter(|o| o.name == e.name)
+                    .all(|o| o.source <= e.source)
+            })
+            .collect()
+    }
+
+    // write_tsv writes effective options with their sources
+    pub fn write_tsv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(["Option", "Value", "Source"].join(SEP).as_bytes())?;
+        writer.write_all(b"\n")?;
+        let mut entries = self.entries();
+        // options are listed in order of help, values in order of definition
+        entries.sort_by_key(|e| OPTIONS.iter().position(|o| o.0 == e.name));
+        for e in entries {
+            let line = [e.name.clone(), e.value.clone(), e.source.to_string()];
+            writer.write_all(line.join(SEP).as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()
+    }
+}
+
+// user_config_path returns $SAD_CONFIG, $XDG_CONFIG_HOME/sad/config.toml
+// or $HOME/.config/sad/config.toml
+fn user_config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(CONFIG_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("sad").join("config.toml"))
+}
+
+// check_option checks that option can be set in config
+fn check_option(name: &str, values: &[String]) -> Result<(), String> {
+    match OPTIONS.iter().find(|o| o.0 == name && o.0 != "help") {
+        Some(o) if !o.3 && values.iter().any(|v| v != "true" && v != "false") => {
+            Err(format!("option {} expects true or false", name))
+        }
+        Some(_) => Ok(()),
+        None => Err(format!("unknown option {}", name)),
+    }
+}
+
+// scalar returns text of toml value, tables and nested arrays
+// are not option values
+fn scalar(key: &str, value: toml::Value) -> Result<String, String> {
+    match value {
+        toml::Value::String(s) => Ok(s),
+        toml::Value::Integer(i) => Ok(i.to_string()),
+        toml::Value::Float(f) => Ok(f.to_string()),
+        toml::Value::Boolean(b) => Ok(b.to_string()),
+        toml::Value::Datetime(d) => Ok(d.to_string()),
+        toml::Value::Array(_) | toml::Value::Table(_) => Err(format!(
+            "option {} expects a string, boolean, number or array of them",
+            key
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str;
+
+    #[test]
+    fn test_config() {
+        let mut config = Config::default();
+        let user = Source::UserConfig(PathBuf::from("config.toml"));
+        config
+            .add_toml(
+                "# user defaults
+format = \"json\"
+sort_by_start_date = true
+where = ['Category=Dairy', \"Currency=EUR\"] # default filters",
+                user.clone(),
+            )
+            .unwrap();
+        config
+            .add_toml(
+                "columns = \"Project, Category\"\nwhere = \"Responsible=Clark Kent\"",
+                Source::ProjectConfig(PathBuf::from("sad.toml")),
+            )
+            .unwrap();
+        config
+            .add_env(vec![
+                ("SAD_FORMAT".to_string(), "tsv".to_string()),
+                ("HOME".to_string(), "/root".to_string()),
+            ])
+            .unwrap();
+        config.add("columns", "Project", Source::CommandLine);
+
+        let mut buf: Vec<u8> = Vec::new();
+        config.write_tsv(&mut buf).unwrap();
+        assert_eq!(
+            str::from_utf8(&buf).unwrap(),
+            "Option\tValue\tSource
+where\tResponsible=Clark Kent\tsad.toml
+sort-by-start-date\ttrue\tconfig.toml
+columns\tProject\tcommand line
+format\ttsv\tenv SAD_FORMAT
+join-kind\tleft\tdefault
+key\tProject\tdefault
+on-conflict\tfirst\tdefault
+"
+        );
+
+        assert!(config.add_toml("unknown = 1", user.clone()).is_err());
+        assert!(config
+            .add_toml("sort-by-start-date = \"yes\"", user.clone())
+            .is_err());
+        assert_eq!(
+            config.add_toml("[list]\nlimit = 1", user.clone()),
+            Err("option list expects a string, boolean, number or array of them".to_string())
+        );
+        assert!(config
+            .add_toml("where = [[\"Category=Dairy\"]]", user.clone())
+            .is_err());
+        assert!(config.add_toml("where = \"Category", user.clone()).is_err());
+        // keys are defined once
+        assert!(config
+            .add_toml("limit = 1\nlimit = 2", user.clone())
+            .is_err());
+
+        // arrays can span lines
+        let mut config = Config::default();
+        config
+            .add_toml(
+                "where = [\n  \"Category=Dairy\", # dairy\n  'Currency=EUR',\n]\nlimit = 10",
+                user.clone(),
+            )
+            .unwrap();
+        assert_eq!(
+            config
+                .entries()
+                .iter()
+                .filter(|e| e.source == user)
+                .map(|e| (e.name.as_str(), e.value.as_str()))
+                .collect::<Vec<(&str, &str)>>(),
+            vec![
+                ("where", "Category=Dairy"),
+                ("where", "Currency=EUR"),
+                ("limit", "10")
+            ]
+        );
+        assert!(config
+            .add_env(vec![(
+                "SAD_SORT_BY_START_DATE".to_string(),
+                "1".to_string()
+            )])
+            .is_err());
+        config
+            .add_env(vec![("SAD_UNKNOWN".to_string(), "1".to_string())])
+            .unwrap();
+        assert!(config.entries().iter().all(|e| e.name != "unknown"));
+    }
+}
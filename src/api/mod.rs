@@ -1,67 +1,1156 @@
+pub mod config;
+
+pub use self::config::{Config, Source};
+use pivot::{Aggregate, Pivot};
+use search::Search;
+use series::Period;
+use std::fmt;
 use std::path::PathBuf;
+use storage::{ConflictPolicy, Filter, JoinKind, Page, Ranking};
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    List,
+    Stats,
+    Series,
+    Pivot,
+    // run sql query over projects
+    Query,
+    Validate,
+    Diff,
+    Convert,
+    Update,
+    Delete,
+    Merge,
+    // answer http queries over projects
+    Serve,
+    // print effective configuration
+    Config,
+    Help,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Tsv,
+    Json,
+    // aligned text table
+    Table,
+    // table of sqlite database
+    Sqlite,
+    // columnar parquet file
+    Parquet,
+    // excel workbook
+    Xlsx,
+    // markdown table
+    Markdown,
+    // standalone html report
+    Html,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_lowercase())
+    }
+}
+
+pub const HELP: &str = r#"Usage: sad [COMMAND] [OPTION]... [FILE]...
+Process and transform projects' information in tsv format.
+
+Commands:
+  list                         print projects (default)
+  stats                        print profile of projects
+  series                       print number and savings of projects by start date period
+  pivot                        print pivot table of projects
+  query <sql>                  print result of sql query over projects of table input, e.g.
+                               "SELECT Category, sum(\"Savings amount\") FROM input
+                               WHERE Complexity <> 'Simple' GROUP BY Category ORDER BY 2 DESC"
+  validate                     check the input file and report all invalid lines
+  diff                         compare the input file with another file
+  convert                      print projects in another format
+  update                       set column values of matching projects in the input file
+  delete                       remove matching projects from the input file
+  merge                        merge projects of other files into the input file by project id
+  serve                        answer http queries over projects of the input file
+  config show                  print effective options and where their values come from
+
+The first FILE is the input file, the following ones are files to compare or
+merge with. Single dash options of previous versions (e.g. -File, -Project,
+-SortByStartDate) are accepted as aliases of the long options.
+
+Default options are read from user config ($SAD_CONFIG or ~/.config/sad/config.toml),
+then sad.toml in current directory, then SAD_<OPTION> environment variables
+(e.g. SAD_SORT_BY), options of each source replace the previous ones.
+
+Options:
+  -f, --file <path>            input file
+      --dir <path>             directory of relative input paths of config files
+  -p, --project <project id>   filter results by column "Project"
+  -w, --where <column>=<value> filter results by column value, can be repeated
+      --search <query>         filter results by words or "phrases" in Description,
+                               Category or Responsible ignoring case and accents,
+                               all words must match unless OR is between them
+      --relevance              order list by relevance to --search, adds column
+                               "Relevance", matches are highlighted in table format
+      --sort-by-start-date     sort results by column "Start date" in ascending order
+  -s, --sort-by <column>       sort results by column in ascending order
+  -c, --columns <column>[,...] output only listed columns in listed order
+      --limit <n>              output at most n projects after filtering and sorting
+      --offset <n>             skip first n projects after filtering and sorting
+      --page <n>               output page n of pages of --limit projects, from 1
+      --top <n>                keep projects ranked n or better by --rank-by column
+                               within groups of --by column, adds column "Rank"
+      --rank-by <column>       numeric column to rank projects by in descending order,
+                               default "Savings amount" which is ranked per currency
+      --ranking <ranking>      ranks of equal values: standard (1 2 2 4, default)
+                               or dense (1 2 2 3)
+      --period <period>        series period: day, week, month (default), quarter or year
+      --by <column>            split series or ranking by column values
+      --rows <column>          column of pivot rows
+      --cols <column>          column of pivot columns
+      --values <column>        numeric column aggregated in pivot cells, projects are
+                               counted by default
+      --aggregate <function>   count, sum (default with --values), mean, min or max
+      --format <format>        output format: tsv (default), json, table or markdown
+                               (not for diff), sqlite (list and convert to --output
+                               database), parquet, xlsx or html (list and convert)
+      --table <name>           table of sqlite database, default projects
+      --summary-by <column>    add sheet of project counts and savings totals
+                               per column value to xlsx output
+      --template <path>        write projects of list and convert with template file
+                               of {{header}}, {{row}} and {{footer}} sections, e.g.
+                               "Project {Project} saves {Savings amount|money}"
+  -o, --output <path>          write output to file instead of stdout, the file is
+                               replaced only when output is complete
+      --no-overwrite           fail if output file exists
+      --split <template>       write projects to files named by column values,
+                               e.g. out/{Category}.tsv, instead of stdout
+  -j, --join <path>            add columns of lookup file to projects, can be repeated
+      --on <column>            column to match with lookup file of the preceding --join,
+                               first column of lookup file by default
+      --join-kind <inner|left> drop (inner) or keep (left, default) unmatched projects
+  -d, --derive <name>=<expr>   add column computed from other columns, can be repeated,
+                               e.g. "Start year = year(Start date)",
+                               "Savings kEUR = Savings amount / 1000",
+                               "Age days = today - Start date"
+      --with <path>            file to compare with on diff or to merge, can be repeated
+  -k, --key <column>[,...]     key columns to match projects on diff, default "Project"
+      --on-conflict <policy>   which project to keep on merge conflict:
+                               first (default), last, newest (by start date) or fail
+      --set <column>=<value>   value to set on update, can be repeated
+      --listen <address>       address of serve, default 127.0.0.1:8080
+      --watch                  reload the input file when it changes on list and serve,
+                               changes are applied only if the file is valid
+  -h, --help                   print this help"#;
+
+// OPTIONS lists command line options as long name, short name,
+// legacy single dash name and whether option takes value
+#[rustfmt::skip]
+const OPTIONS: [(&str, Option<char>, &str, bool); 39] = [
+    ("file", Some('f'), "-File", true),
+    ("dir", None, "", true),
+    ("project", Some('p'), "-Project", true),
+    ("where", Some('w'), "-Where", true),
+    ("search", None, "-Search", true),
+    ("relevance", None, "", false),
+    ("sort-by-start-date", None, "-SortByStartDate", false),
+    ("sort-by", Some('s'), "-SortBy", true),
+    ("columns", Some('c'), "-Columns", true),
+    ("limit", None, "-Limit", true),
+    ("offset", None, "-Offset", true),
+    ("page", None, "-Page", true),
+    ("top", None, "", true),
+    ("rank-by", None, "", true),
+    ("ranking", None, "", true),
+    ("period", None, "", true),
+    ("by", None, "", true),
+    ("rows", None, "", true),
+    ("cols", None, "", true),
+    ("values", None, "", true),
+    ("aggregate", None, "", true),
+    ("format", None, "-Format", true),
+    ("table", None, "", true),
+    ("summary-by", None, "", true),
+    ("template", None, "", true),
+    ("output", Some('o'), "-Output", true),
+    ("no-overwrite", None, "-NoOverwrite", false),
+    ("split", None, "", true),
+    ("join", Some('j'), "-Join", true),
+    ("on", None, "-On", true),
+    ("join-kind", None, "-JoinKind", true),
+    ("derive", Some('d'), "-Derive", true),
+    ("with", None, "-With", true),
+    ("key", Some('k'), "-Key", true),
+    ("on-conflict", None, "-OnConflict", true),
+    ("set", None, "-Set", true),
+    ("listen", None, "", true),
+    ("watch", None, "", false),
+    ("help", Some('h'), "-help", false),
+];
 
 #[derive(Debug)]
 pub struct Cli {
+    pub command: Command,
     pub file: PathBuf,
+    // directory of input files with relative paths
+    pub dir: Option<PathBuf>,
     pub sort_by_start_date: bool,
     pub project: Option<String>,
+    // column, value pairs projects should be equal to
+    pub conditions: Vec<(String, String)>,
+    // full text query projects should match
+    pub search: Option<Search>,
+    // order projects by relevance to search
+    pub relevance: bool,
+    // column, value pairs to set on update
+    pub assignments: Vec<(String, String)>,
+    // files to compare or merge input file with
+    pub with: Vec<PathBuf>,
+    // key columns to match projects on diff
+    pub key: Vec<String>,
+    // number of projects to skip and to output after filtering and sorting,
+    // or number of page of limit projects
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub page: Option<usize>,
+    // number of best ranked projects to keep, column to rank by and ranking
+    pub top: Option<usize>,
+    pub rank_by: Option<String>,
+    pub ranking: Option<Ranking>,
+    // series period and column to split series or ranking by
+    pub period: Period,
+    pub by: Option<String>,
+    // pivot rows, columns and values columns
+    pub rows: Option<String>,
+    pub cols: Option<String>,
+    pub values: Option<String>,
+    pub aggregate: Option<Aggregate>,
+    pub format: Format,
+    // table of sqlite input or output
+    pub table: String,
+    // column of groups summarized on second sheet of xlsx output
+    pub summary_by: Option<String>,
+    // template file projects are written with instead of format
+    pub template: Option<PathBuf>,
+    // file to write output to instead of stdout
+    pub output: Option<PathBuf>,
+    // fail instead of replacing existing output file
+    pub no_overwrite: bool,
+    // path template of files to split output into
+    pub split: Option<String>,
+    pub on_conflict: ConflictPolicy,
+    // lookup files with optional key column to join projects with
+    pub joins: Vec<(PathBuf, Option<String>)>,
+    pub join_kind: JoinKind,
+    pub sort_by: Option<String>,
+    // derived column definitions `<name> = <expression>`
+    pub derived: Vec<String>,
+    // output columns, all by default
+    pub columns: Vec<String>,
+    // sql text of query command
+    pub query: Option<String>,
+    // address serve listens on
+    pub listen: String,
+    // reload input file on change
+    pub watch: bool,
+    // options of all sources cli was configured with
+    pub config: Config,
+}
+
+impl Default for Cli {
+    fn default() -> Cli {
+        Cli {
+            command: Command::List,
+            file: PathBuf::new(),
+            dir: None,
+            sort_by_start_date: false,
+            project: None,
+            conditions: vec![],
+            search: None,
+            relevance: false,
+            assignments: vec![],
+            with: vec![],
+            key: vec![],
+            offset: 0,
+            limit: None,
+            page: None,
+            top: None,
+            rank_by: None,
+            ranking: None,
+            period: Period::Month,
+            by: None,
+            rows: None,
+            cols: None,
+            values: None,
+            aggregate: None,
+            format: Format::Tsv,
+            table: "projects".to_string(),
+            summary_by: None,
+            template: None,
+            output: None,
+            no_overwrite: false,
+            split: None,
+            on_conflict: ConflictPolicy::FirstWins,
+            joins: vec![],
+            join_kind: JoinKind::Left,
+            sort_by: None,
+            derived: vec![],
+            columns: vec![],
+            query: None,
+            listen: "127.0.0.1:8080".to_string(),
+            watch: false,
+            config: Config::default(),
+        }
+    }
 }
 
 impl Cli {
-    pub fn new(args: &[String]) -> Result<Cli, &str> {
-        let help_msg = r#"
-Usage: sad [OPTION]... -File [FILE]...
-Process and transform projects' information in tsv format.
+    // new configures cli from command line arguments and default options
+    pub fn new(args: &[String]) -> Result<Cli, String> {
+        Cli::with_config(args, Config::default())
+    }
 
-Mandatory arguments:
+    // with_config configures cli from command line arguments
+    // which replace options of config
+    pub fn with_config(args: &[String], mut config: Config) -> Result<Cli, String> {
+        if args.len() < 2 {
+            return Err(format!("not enough arguments\n{}", TRY_HELP));
+        }
 
--File <path>             full path to the input file
+        let mut command = Command::List;
+        let mut iter = args.iter().skip(1).peekable();
+        if let Some(cmd) = iter.peek() {
+            let cmd = match cmd.as_ref() {
+                "list" => Some(Command::List),
+                "stats" => Some(Command::Stats),
+                "series" => Some(Command::Series),
+                "pivot" => Some(Command::Pivot),
+                "query" => Some(Command::Query),
+                "validate" => Some(Command::Validate),
+                "diff" => Some(Command::Diff),
+                "convert" => Some(Command::Convert),
+                "update" => Some(Command::Update),
+                "delete" => Some(Command::Delete),
+                "merge" => Some(Command::Merge),
+                "serve" => Some(Command::Serve),
+                "config" => Some(Command::Config),
+                "help" => Some(Command::Help),
+                _ => None,
+            };
+            if let Some(cmd) = cmd {
+                command = cmd;
+                iter.next();
+            }
+        }
+        if command == Command::Config && iter.next().is_none_or(|s| s != "show") {
+            return Err(format!("expected config show\n{}", TRY_HELP));
+        }
 
-Operation modifiers:
+        let mut files = vec![];
+        let mut has_file = false;
+        let mut only_files = false;
+        while let Some(arg) = iter.next() {
+            if only_files || arg == "-" || !arg.starts_with('-') {
+                files.push(arg);
+                continue;
+            }
+            if arg == "--" {
+                only_files = true;
+                continue;
+            }
 
--SortByStartDate         sort results by column "Start date" in ascending order
--Project <project id>    filter results by column "Project""#;
-        if args.len() < 2 {
-            return Err("not enough arguments");
+            let (long, takes_value, value) = find_option(arg)?;
+            let value = match (takes_value, value) {
+                (true, Some(v)) => v,
+                (true, None) => match iter.next() {
+                    Some(v) => v.to_owned(),
+                    None => return Err(format!("option {} requires a value\n{}", arg, TRY_HELP)),
+                },
+                (false, Some(_)) => return Err(format!("option {} takes no value", arg)),
+                (false, None) => "true".to_string(),
+            };
+            has_file = has_file || long == "file";
+            config.add(long, &value, Source::CommandLine);
+        }
+        // query text precedes files of query command
+        let query = match command {
+            Command::Query if !files.is_empty() => Some(files.remove(0).to_owned()),
+            _ => None,
+        };
+        // the first file is the input file unless it is set by option,
+        // the following ones are compared or merged with
+        for (i, f) in files.iter().enumerate() {
+            let name = if i == 0 && !has_file { "file" } else { "with" };
+            config.add(name, f, Source::CommandLine);
         }
 
         let mut cli = Cli {
-            file: PathBuf::new(),
-            sort_by_start_date: false,
-            project: None,
+            command,
+            query,
+            ..Cli::default()
         };
+        // relative paths of config files are in --dir,
+        // paths of other sources are as given
+        let dir = config
+            .entries()
+            .iter()
+            .rev()
+            .find(|e| e.name == "dir")
+            .map(|e| PathBuf::from(&e.value));
+        for e in config.entries() {
+            let value = match (&dir, &e.source) {
+                (Some(dir), Source::UserConfig(_)) | (Some(dir), Source::ProjectConfig(_))
+                    if ["file", "with", "join"].contains(&e.name.as_str()) =>
+                {
+                    dir.join(&e.value).to_string_lossy().into_owned()
+                }
+                _ => e.value.clone(),
+            };
+            // flags can be turned off by config
+            let flag = OPTIONS.iter().any(|o| o.0 == e.name && !o.3);
+            if !(flag && value == "false") {
+                cli.apply(&e.name, Some(&value))?;
+            }
+        }
+        cli.config = config;
 
-        let mut iter = args.iter();
-        // skip bin name
-        iter.next();
-        while let Some(arg) = iter.next() {
-            match arg.as_ref() {
-                "-File" => {
-                    if let Some(path) = iter.next() {
-                        cli.file = PathBuf::from(path);
-                    } else {
-                        return Err("input file not defined");
+        cli.check()?;
+        Ok(cli)
+    }
+
+    // apply sets option by its long name
+    pub fn apply(&mut self, name: &str, value: Option<&str>) -> Result<(), String> {
+        let v = value.unwrap_or_default();
+        match name {
+            "file" => self.file = PathBuf::from(v),
+            "dir" => self.dir = Some(PathBuf::from(v)),
+            "project" => self.project = Some(v.to_owned()),
+            "where" => match split_pair(v) {
+                Some(pair) => self.conditions.push(pair),
+                None => return Err("--where expects <column>=<value>".to_string()),
+            },
+            "search" => self.search = Some(Search::parse(v)?),
+            "relevance" => self.relevance = true,
+            "sort-by-start-date" => self.sort_by_start_date = true,
+            "sort-by" => self.sort_by = Some(v.to_owned()),
+            "columns" => self.columns = split_list(v),
+            "limit" => match v.parse() {
+                Ok(n) => self.limit = Some(n),
+                _ => return Err(format!("--limit expects a number, got {}", v)),
+            },
+            "offset" => match v.parse() {
+                Ok(n) => self.offset = n,
+                _ => return Err(format!("--offset expects a number, got {}", v)),
+            },
+            "page" => match v.parse() {
+                Ok(n) if n > 0 => self.page = Some(n),
+                _ => return Err(format!("--page expects positive number, got {}", v)),
+            },
+            "top" => match v.parse() {
+                Ok(n) if n > 0 => self.top = Some(n),
+                _ => return Err(format!("--top expects positive number, got {}", v)),
+            },
+            "rank-by" => self.rank_by = Some(v.to_owned()),
+            "ranking" => {
+                self.ranking = match v {
+                    "standard" => Some(Ranking::Standard),
+                    "dense" => Some(Ranking::Dense),
+                    _ => return Err("--ranking expects standard or dense".to_string()),
+                }
+            }
+            "period" => self.period = Period::parse(v)?,
+            "by" => self.by = Some(v.to_owned()),
+            "rows" => self.rows = Some(v.to_owned()),
+            "cols" => self.cols = Some(v.to_owned()),
+            "values" => self.values = Some(v.to_owned()),
+            "aggregate" => self.aggregate = Some(Aggregate::parse(v)?),
+            "format" => {
+                self.format = match v {
+                    "tsv" => Format::Tsv,
+                    "json" => Format::Json,
+                    "table" => Format::Table,
+                    "sqlite" => Format::Sqlite,
+                    "parquet" => Format::Parquet,
+                    "xlsx" => Format::Xlsx,
+                    "markdown" => Format::Markdown,
+                    "html" => Format::Html,
+                    _ => {
+                        return Err(format!(
+                            "unsupported format {}, expected tsv, json, table, markdown, \
+                             html, sqlite, parquet or xlsx",
+                            v
+                        ))
                     }
                 }
-                "-SortByStartDate" => {
-                    cli.sort_by_start_date = true;
+            }
+            "table" => self.table = v.to_owned(),
+            "summary-by" => self.summary_by = Some(v.to_owned()),
+            "template" => self.template = Some(PathBuf::from(v)),
+            "output" => self.output = Some(PathBuf::from(v)),
+            "no-overwrite" => self.no_overwrite = true,
+            "split" => self.split = Some(v.to_owned()),
+            "join" => self.joins.push((PathBuf::from(v), None)),
+            "on" => match self.joins.last_mut() {
+                Some(join) => join.1 = Some(v.to_owned()),
+                None => return Err("--on must follow --join".to_string()),
+            },
+            "join-kind" => {
+                self.join_kind = match v {
+                    "inner" => JoinKind::Inner,
+                    "left" => JoinKind::Left,
+                    _ => return Err("--join-kind expects inner or left".to_string()),
                 }
-                "-Project" => {
-                    if let Some(id) = iter.next() {
-                        cli.project = Some(id.to_owned());
-                    } else {
-                        return Err("project id missing");
+            }
+            "derive" => self.derived.push(v.to_owned()),
+            "with" => self.with.push(PathBuf::from(v)),
+            "key" => self.key = split_list(v),
+            "on-conflict" => {
+                self.on_conflict = match v {
+                    "first" => ConflictPolicy::FirstWins,
+                    "last" => ConflictPolicy::LastWins,
+                    "newest" => ConflictPolicy::NewestStartDate,
+                    "fail" => ConflictPolicy::Fail,
+                    _ => {
+                        return Err("--on-conflict expects first, last, newest or fail".to_string())
                     }
                 }
-                "-help" => {
-                    return Err(help_msg);
+            }
+            "set" => match split_pair(v) {
+                Some(pair) => self.assignments.push(pair),
+                None => return Err("--set expects <column>=<value>".to_string()),
+            },
+            "listen" => self.listen = v.to_owned(),
+            "watch" => self.watch = true,
+            "help" if value.is_none_or(|v| v == "true") => self.command = Command::Help,
+            _ => return Err(format!("unrecognized option {}\n{}", name, TRY_HELP)),
+        }
+        Ok(())
+    }
+
+    // check validates options required by command
+    fn check(&mut self) -> Result<(), String> {
+        if self.command == Command::Help || self.command == Command::Config {
+            return Ok(());
+        }
+        if self.file.as_os_str().is_empty() {
+            return Err(format!("input file not defined\n{}", TRY_HELP));
+        }
+        match self.command {
+            Command::Update | Command::Delete
+                if self.project.is_none() && self.conditions.is_empty() =>
+            {
+                return Err("update and delete require --project or --where filter".to_string());
+            }
+            Command::Update if self.assignments.is_empty() => {
+                return Err("update requires at least one --set".to_string());
+            }
+            Command::Diff if self.with.len() != 1 => {
+                return Err("diff requires one file to compare with".to_string());
+            }
+            Command::Diff if matches!(self.format, Format::Table | Format::Markdown) => {
+                return Err(format!("diff does not support {} format", self.format));
+            }
+            Command::Query if self.query.is_none() => {
+                return Err(format!("query requires sql text\n{}", TRY_HELP));
+            }
+            Command::Pivot if self.rows.is_none() || self.cols.is_none() => {
+                return Err("pivot requires --rows and --cols".to_string());
+            }
+            Command::Merge if self.with.is_empty() => {
+                return Err("merge requires at least one file to merge with".to_string());
+            }
+            _ if self.split.is_some() && self.output.is_some() => {
+                return Err("--split and --output can not be used together".to_string());
+            }
+            Command::List | Command::Convert
+                if self.format == Format::Sqlite && self.output.is_none() =>
+            {
+                return Err("sqlite format requires --output database".to_string());
+            }
+            Command::List | Command::Convert => {}
+            _ if matches!(
+                self.format,
+                Format::Sqlite | Format::Parquet | Format::Xlsx | Format::Html
+            ) =>
+            {
+                return Err(format!(
+                    "{} format is supported by list and convert only",
+                    self.format
+                ));
+            }
+            _ => {}
+        }
+        if self.summary_by.is_some() && self.format != Format::Xlsx {
+            return Err("--summary-by requires xlsx format".to_string());
+        }
+        if self.relevance {
+            if self.command != Command::List {
+                return Err("--relevance is supported by list only".to_string());
+            }
+            if self.search.is_none() {
+                return Err("--relevance requires --search".to_string());
+            }
+            if self.sort_by.is_some() || self.sort_by_start_date {
+                return Err("--relevance can not be used with sorting".to_string());
+            }
+        }
+        if self.limit.is_some() || self.offset > 0 || self.page.is_some() {
+            if !matches!(self.command, Command::List | Command::Convert) {
+                return Err(
+                    "--limit, --offset and --page are supported by list and convert only"
+                        .to_string(),
+                );
+            }
+            self.pagination()?;
+        }
+        if self.template.is_some() {
+            if !matches!(self.command, Command::List | Command::Convert) {
+                return Err("--template is supported by list and convert only".to_string());
+            }
+            if self.format != Format::Tsv {
+                return Err("--template can not be used with --format".to_string());
+            }
+        }
+        match self.command {
+            Command::List | Command::Serve => {}
+            _ if self.watch => {
+                return Err("--watch is supported by list and serve only".to_string());
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    // pagination returns page of projects to output, --page numbers
+    // pages of --limit projects
+    pub fn pagination(&self) -> Result<Page, String> {
+        match (self.page, self.limit) {
+            (None, limit) => Ok(Page::new(self.offset, limit)),
+            (Some(_), None) => Err("--page requires --limit as page size".to_string()),
+            (Some(_), Some(_)) if self.offset > 0 => {
+                Err("--page can not be used with --offset".to_string())
+            }
+            (Some(n), Some(size)) => Page::number(n, size),
+        }
+    }
+
+    // is_ranked reports whether projects should be ranked
+    pub fn is_ranked(&self) -> bool {
+        self.top.is_some() || self.rank_by.is_some() || self.ranking.is_some()
+    }
+
+    // pivot returns pivot of options, values are summed by default
+    pub fn pivot(&self) -> Option<Pivot> {
+        let aggregate = match (self.aggregate, &self.values) {
+            (Some(a), _) => a,
+            (None, Some(_)) => Aggregate::Sum,
+            (None, None) => Aggregate::Count,
+        };
+        Some(Pivot {
+            rows: self.rows.clone()?,
+            columns: self.cols.clone()?,
+            values: self.values.clone(),
+            aggregate,
+        })
+    }
+
+    // filter returns predicate matching projects by -Project, -Where
+    // and -Search options
+    pub fn filter(&self) -> Filter {
+        let project_id = self.project.clone();
+        let conditions = self.conditions.clone();
+        let search = self.search.clone();
+        Box::new(move |p| {
+            if let Some(id) = &project_id {
+                if id != &p.id {
+                    return false;
                 }
-                _ => {
-                    return Err("unrecognized option {}\nTry '-help' for more information.");
+            }
+            if let Some(search) = &search {
+                if !search.matches(p) {
+                    return false;
                 }
             }
+            conditions
+                .iter()
+                .all(|(c, v)| p.get(c).is_some_and(|pv| &pv == v))
+        })
+    }
+}
+
+const TRY_HELP: &str = "Try 'sad --help' for more information.";
+
+// find_option finds option by `--long[=value]`, `-s` or legacy `-Name`,
+// returns its long name, whether it takes value and value if given inline
+fn find_option(arg: &str) -> Result<(&'static str, bool, Option<String>), String> {
+    let found = if let Some(long) = arg.strip_prefix("--") {
+        let mut parts = long.splitn(2, '=');
+        let name = parts.next().unwrap_or_default();
+        let value = parts.next().map(|v| v.to_owned());
+        OPTIONS.iter().find(|o| o.0 == name).map(|o| (o, value))
+    } else {
+        let mut chars = arg.chars().skip(1);
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => OPTIONS.iter().find(|o| o.1 == Some(c)),
+            _ => OPTIONS.iter().find(|o| o.2 == arg),
         }
+        .map(|o| (o, None))
+    };
 
-        Ok(cli)
+    match found {
+        Some((o, value)) => Ok((o.0, o.3, value)),
+        None => Err(format!("unrecognized option {}\n{}", arg, TRY_HELP)),
+    }
+}
+
+fn split_list(s: &str) -> Vec<String> {
+    s.split(',').map(|c| c.trim().to_string()).collect()
+}
+
+fn split_pair(s: &str) -> Option<(String, String)> {
+    let mut parts = s.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(c), Some(v)) if !c.is_empty() => Some((c.to_string(), v.to_string())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::*;
+
+    fn args(s: &[&str]) -> Vec<String> {
+        s.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_cli_commands() {
+        let cli = Cli::new(&args(&["sad", "-File", "a.tsv", "-Project", "2"])).unwrap();
+        assert_eq!(cli.command, Command::List);
+
+        let cli = Cli::new(&args(&[
+            "sad",
+            "update",
+            "-File",
+            "a.tsv",
+            "-Where",
+            "Category=Dairy",
+            "-Set",
+            "Currency=EUR",
+        ]))
+        .unwrap();
+        assert_eq!(cli.command, Command::Update);
+        assert_eq!(
+            cli.conditions,
+            vec![("Category".to_string(), "Dairy".to_string())]
+        );
+        assert_eq!(
+            cli.assignments,
+            vec![("Currency".to_string(), "EUR".to_string())]
+        );
+
+        let cli = Cli::new(&args(&[
+            "sad",
+            "diff",
+            "-File",
+            "a.tsv",
+            "-With",
+            "b.tsv",
+            "-Key",
+            "Project, Category",
+            "-Format",
+            "json",
+        ]))
+        .unwrap();
+        assert_eq!(cli.command, Command::Diff);
+        assert_eq!(cli.with, vec![PathBuf::from("b.tsv")]);
+        assert_eq!(cli.key, vec!["Project", "Category"]);
+        assert_eq!(cli.format, Format::Json);
+
+        assert!(Cli::new(&args(&["sad", "diff", "-File", "a.tsv"])).is_err());
+
+        let cli = Cli::new(&args(&[
+            "sad",
+            "-File",
+            "a.tsv",
+            "-Join",
+            "b.tsv",
+            "-On",
+            "Responsible",
+            "-Join",
+            "c.tsv",
+            "-JoinKind",
+            "inner",
+            "-SortBy",
+            "Department",
+        ]))
+        .unwrap();
+        assert_eq!(
+            cli.joins,
+            vec![
+                (PathBuf::from("b.tsv"), Some("Responsible".to_string())),
+                (PathBuf::from("c.tsv"), None)
+            ]
+        );
+        assert_eq!(cli.join_kind, JoinKind::Inner);
+        assert_eq!(cli.sort_by, Some("Department".to_string()));
+
+        let cli = Cli::new(&args(&[
+            "sad",
+            "-File",
+            "a.tsv",
+            "-Derive",
+            "Year = year(Start date)",
+            "-Columns",
+            "Project, Year",
+        ]))
+        .unwrap();
+        assert_eq!(cli.derived, vec!["Year = year(Start date)"]);
+        assert_eq!(cli.columns, vec!["Project", "Year"]);
+        assert!(Cli::new(&args(&["sad", "-File", "a.tsv", "-On", "Category"])).is_err());
+
+        let cli = Cli::new(&args(&[
+            "sad",
+            "merge",
+            "-File",
+            "a.tsv",
+            "-With",
+            "b.tsv",
+            "-With",
+            "c.tsv",
+            "-OnConflict",
+            "newest",
+        ]))
+        .unwrap();
+        assert_eq!(cli.command, Command::Merge);
+        assert_eq!(cli.with.len(), 2);
+        assert_eq!(cli.on_conflict, ConflictPolicy::NewestStartDate);
+        assert!(Cli::new(&args(&[
+            "sad",
+            "merge",
+            "-File",
+            "a.tsv",
+            "-OnConflict",
+            "any"
+        ]))
+        .is_err());
+        assert!(Cli::new(&args(&["sad", "delete", "-File", "a.tsv"])).is_err());
+        assert!(Cli::new(&args(&["sad", "update", "-File", "a.tsv", "-Project", "2"])).is_err());
+        assert!(Cli::new(&args(&["sad", "-File", "a.tsv", "-Set", "Currency"])).is_err());
+    }
+
+    #[test]
+    fn test_cli_long_options() {
+        let cli = Cli::new(&args(&[
+            "sad",
+            "list",
+            "-p",
+            "2",
+            "--where=Category=Dairy",
+            "--sort-by",
+            "Start date",
+            "-c",
+            "Project,Category",
+            "a.tsv",
+        ]))
+        .unwrap();
+        assert_eq!(cli.command, Command::List);
+        assert_eq!(cli.file, PathBuf::from("a.tsv"));
+        assert_eq!(cli.project, Some("2".to_string()));
+        assert_eq!(
+            cli.conditions,
+            vec![("Category".to_string(), "Dairy".to_string())]
+        );
+        assert_eq!(cli.sort_by, Some("Start date".to_string()));
+        assert_eq!(cli.columns, vec!["Project", "Category"]);
+
+        // files after the input file are compared with
+        let cli = Cli::new(&args(&[
+            "sad", "diff", "--format", "json", "a.tsv", "b.tsv",
+        ]))
+        .unwrap();
+        assert_eq!(cli.file, PathBuf::from("a.tsv"));
+        assert_eq!(cli.with, vec![PathBuf::from("b.tsv")]);
+        assert_eq!(cli.key, vec!["Project"]);
+
+        let cli = Cli::new(&args(&["sad", "validate", "--", "-a.tsv"])).unwrap();
+        assert_eq!(cli.command, Command::Validate);
+        assert_eq!(cli.file, PathBuf::from("-a.tsv"));
+
+        assert_eq!(
+            Cli::new(&args(&["sad", "--help"])).unwrap().command,
+            Command::Help
+        );
+        assert_eq!(
+            Cli::new(&args(&["sad", "help"])).unwrap().command,
+            Command::Help
+        );
+
+        assert_eq!(
+            Cli::new(&args(&["sad", "stats", "--file"])).unwrap_err(),
+            "option --file requires a value\nTry 'sad --help' for more information."
+        );
+        assert_eq!(
+            Cli::new(&args(&["sad", "-Unknown", "a.tsv"])).unwrap_err(),
+            "unrecognized option -Unknown\nTry 'sad --help' for more information."
+        );
+
+        assert!(Cli::new(&args(&["sad", "--sort-by-start-date=yes", "a.tsv"])).is_err());
+        assert!(Cli::new(&args(&["sad", "convert"])).is_err());
+    }
+
+    #[test]
+    fn test_cli_output() {
+        let cli = Cli::new(&args(&["sad", "a.tsv", "-Output", "b.tsv", "-NoOverwrite"])).unwrap();
+        assert_eq!(cli.output, Some(PathBuf::from("b.tsv")));
+        assert!(cli.no_overwrite);
+    }
+
+    #[test]
+    fn test_cli_top() {
+        let cli = Cli::new(&args(&[
+            "sad",
+            "a.tsv",
+            "--top",
+            "3",
+            "--by",
+            "Category",
+            "--ranking",
+            "dense",
+        ]))
+        .unwrap();
+        assert!(cli.is_ranked());
+        assert_eq!(cli.top, Some(3));
+        assert_eq!(cli.ranking, Some(Ranking::Dense));
+        assert!(Cli::new(&args(&["sad", "a.tsv", "--top", "0"])).is_err());
+    }
+
+    #[test]
+    fn test_cli_watch() {
+        let cli = Cli::new(&args(&["sad", "serve", "a.tsv", "--watch"])).unwrap();
+        assert!(cli.watch);
+        assert!(Cli::new(&args(&["sad", "stats", "a.tsv", "--watch"])).is_err());
+    }
+
+    #[test]
+    fn test_cli_sqlite() {
+        let cli = Cli::new(&args(&[
+            "sad", "convert", "a.tsv", "--format", "sqlite", "-o", "a.db", "--table", "p",
+        ]))
+        .unwrap();
+        assert_eq!((cli.format, cli.table.as_str()), (Format::Sqlite, "p"));
+        assert_eq!(
+            Cli::new(&args(&["sad", "a.tsv", "--format", "sqlite"])).unwrap_err(),
+            "sqlite format requires --output database"
+        );
+        assert!(Cli::new(&args(&[
+            "sad", "stats", "a.tsv", "--format", "sqlite", "-o", "a.db"
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn test_cli_query() {
+        let cli = Cli::new(&args(&["sad", "query", "SELECT * FROM input", "a.tsv"])).unwrap();
+        assert_eq!(cli.query, Some("SELECT * FROM input".to_string()));
+        assert_eq!(cli.file, PathBuf::from("a.tsv"));
+        assert!(Cli::new(&args(&["sad", "query", "-f", "a.tsv"])).is_err());
+    }
+
+    #[test]
+    fn test_cli_parquet() {
+        let cli = Cli::new(&args(&["sad", "convert", "a.tsv", "--format", "parquet"])).unwrap();
+        assert_eq!(cli.format, Format::Parquet);
+        assert_eq!(
+            Cli::new(&args(&["sad", "stats", "a.tsv", "--format", "parquet"])).unwrap_err(),
+            "parquet format is supported by list and convert only"
+        );
+    }
+
+    #[test]
+    fn test_cli_xlsx() {
+        let cli = Cli::new(&args(&[
+            "sad",
+            "a.tsv",
+            "--format",
+            "xlsx",
+            "--summary-by",
+            "Category",
+        ]))
+        .unwrap();
+        assert_eq!(
+            (cli.format, cli.summary_by),
+            (Format::Xlsx, Some("Category".to_string()))
+        );
+        assert_eq!(
+            Cli::new(&args(&["sad", "a.tsv", "--summary-by", "Category"])).unwrap_err(),
+            "--summary-by requires xlsx format"
+        );
+    }
+
+    #[test]
+    fn test_cli_report() {
+        let cli = Cli::new(&args(&["sad", "stats", "a.tsv", "--format", "markdown"])).unwrap();
+        assert_eq!(cli.format, Format::Markdown);
+        assert_eq!(
+            Cli::new(&args(&["sad", "stats", "a.tsv", "--format", "html"])).unwrap_err(),
+            "html format is supported by list and convert only"
+        );
+    }
+
+    #[test]
+    fn test_cli_template() {
+        let cli = Cli::new(&args(&["sad", "convert", "a.tsv", "--template", "t.txt"])).unwrap();
+        assert_eq!(cli.template, Some(PathBuf::from("t.txt")));
+        assert_eq!(
+            Cli::new(&args(&["sad", "stats", "a.tsv", "--template", "t.txt"])).unwrap_err(),
+            "--template is supported by list and convert only"
+        );
+        assert_eq!(
+            Cli::new(&args(&[
+                "sad",
+                "a.tsv",
+                "--template",
+                "t.txt",
+                "--format",
+                "json"
+            ]))
+            .unwrap_err(),
+            "--template can not be used with --format"
+        );
+    }
+
+    #[test]
+    fn test_cli_pagination() {
+        let cli = Cli::new(&args(&["sad", "a.tsv", "-Offset", "2", "-Limit", "3"])).unwrap();
+        assert_eq!(cli.pagination(), Ok(Page::new(2, Some(3))));
+        let cli = Cli::new(&args(&["sad", "a.tsv", "--page", "3", "--limit", "10"])).unwrap();
+        assert_eq!(cli.pagination(), Ok(Page::new(20, Some(10))));
+        assert_eq!(
+            Cli::new(&args(&["sad", "a.tsv", "--page", "3"])).unwrap_err(),
+            "--page requires --limit as page size"
+        );
+        assert_eq!(
+            Cli::new(&args(&[
+                "sad", "a.tsv", "--page", "3", "--limit", "1", "--offset", "1"
+            ]))
+            .unwrap_err(),
+            "--page can not be used with --offset"
+        );
+        assert!(Cli::new(&args(&["sad", "a.tsv", "--page", "0", "--limit", "1"])).is_err());
+        assert!(Cli::new(&args(&["sad", "stats", "a.tsv", "--limit", "1"])).is_err());
+    }
+
+    #[test]
+    fn test_cli_search() {
+        let cli = Cli::new(&args(&[
+            "sad",
+            "a.tsv",
+            "--search",
+            "milk OR cream",
+            "--relevance",
+        ]))
+        .unwrap();
+        assert_eq!(cli.search, Some(Search::parse("milk OR cream").unwrap()));
+        assert!(cli.relevance);
+        assert!(Cli::new(&args(&["sad", "a.tsv", "--search", "milk OR"])).is_err());
+        assert_eq!(
+            Cli::new(&args(&["sad", "a.tsv", "--relevance"])).unwrap_err(),
+            "--relevance requires --search"
+        );
+        assert_eq!(
+            Cli::new(&args(&[
+                "sad",
+                "a.tsv",
+                "--search",
+                "milk",
+                "--relevance",
+                "-s",
+                "Project"
+            ]))
+            .unwrap_err(),
+            "--relevance can not be used with sorting"
+        );
+    }
+
+    #[test]
+    fn test_cli_config() {
+        let mut config = Config::default();
+        config
+            .add_toml(
+                "dir = \"data\"\nfile = \"a.tsv\"\nwhere = [\"Category=Dairy\"]\nsort-by-start-date = false",
+                Source::ProjectConfig(PathBuf::from("sad.toml")),
+            )
+            .unwrap();
+        config
+            .add_env(vec![("SAD_FORMAT".to_string(), "json".to_string())])
+            .unwrap();
+
+        let cli = Cli::with_config(&args(&["sad", "stats"]), config.clone()).unwrap();
+        assert_eq!(cli.file, PathBuf::from("data/a.tsv"));
+        assert_eq!(
+            cli.conditions,
+            vec![("Category".to_string(), "Dairy".to_string())]
+        );
+        assert!(!cli.sort_by_start_date);
+        assert_eq!(cli.format, Format::Json);
+
+        // command line options replace configured ones
+        let cli = Cli::with_config(
+            &args(&["sad", "-w", "Currency=EUR", "--sort-by-start-date", "b.tsv"]),
+            config.clone(),
+        )
+        .unwrap();
+        // paths of command line are not in configured dir
+        assert_eq!(cli.file, PathBuf::from("b.tsv"));
+        assert_eq!(
+            cli.conditions,
+            vec![("Currency".to_string(), "EUR".to_string())]
+        );
+        assert!(cli.sort_by_start_date);
+        let cli = Cli::with_config(
+            &args(&["sad", "diff", "--dir", "other", "--with", "c.tsv"]),
+            config.clone(),
+        )
+        .unwrap();
+        assert_eq!(cli.file, PathBuf::from("other/a.tsv"));
+        assert_eq!(cli.with, vec![PathBuf::from("c.tsv")]);
+
+        // false is only a value of flags
+        let mut env = config.clone();
+        env.add_env(vec![
+            ("SAD_WHERE".to_string(), "Category=false".to_string()),
+            ("SAD_SORT_BY".to_string(), "false".to_string()),
+            ("SAD_OTHER_TOOL".to_string(), "1".to_string()),
+        ])
+        .unwrap();
+        let cli = Cli::with_config(&args(&["sad", "stats"]), env).unwrap();
+        assert_eq!(
+            cli.conditions,
+            vec![("Category".to_string(), "false".to_string())]
+        );
+        assert_eq!(cli.sort_by, Some("false".to_string()));
+
+        let cli = Cli::with_config(&args(&["sad", "config", "show"]), config).unwrap();
+        assert_eq!(cli.command, Command::Config);
+        assert!(Cli::new(&args(&["sad", "config"])).is_err());
+    }
+
+    #[test]
+    fn test_cli_filter() {
+        let cli = Cli::new(&args(&[
+            "sad",
+            "-File",
+            "a.tsv",
+            "-Where",
+            "Savings amount=",
+            "-Where",
+            "Category=Dairy",
+        ]))
+        .unwrap();
+        let filter = cli.filter();
+        assert!(filter(&get_project(1)));
+        assert!(!filter(&get_project(2)));
     }
 }
@@ -1,48 +1,519 @@
+extern crate chrono;
 extern crate sad;
 
+use chrono::Local;
 use std::error::Error;
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::{stdout, BufReader, BufWriter, SeekFrom};
+use std::fs::{self, File};
+use std::io::{self, stdout, BufReader, BufWriter, Write};
+use std::net::TcpListener;
 use std::process;
+use std::thread;
 
-use sad::api::Cli;
-use sad::parser::{parse_tsv, write_tsv};
-use sad::storage::Storage;
+use sad::api::{Cli, Command, Config, Format, HELP};
+#[cfg(feature = "parquet")]
+use sad::columnar;
+use sad::diff;
+use sad::expr::Derived;
+use sad::file;
+use sad::json;
+use sad::parser::{
+    parse_lookup_tsv, try_parse_tsv, write_source_tsv, write_tsv_columns, ParseOptions,
+};
+use sad::partition::{partition, Template};
+use sad::query::Query;
+use sad::report;
+use sad::series::series;
+use sad::server;
+#[cfg(feature = "sqlite")]
+use sad::sqlite;
+use sad::stats::Stats;
+use sad::storage::{Ranking, Storage};
+use sad::table::Table;
+use sad::template;
+use sad::watch::{self, Watcher};
+#[cfg(feature = "xlsx")]
+use sad::xlsx;
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() {
     let args: Vec<String> = std::env::args().collect();
-    // configure cli
-    let opts = Cli::new(&args).unwrap_or_else(|err| {
-        eprintln!("{}", err);
+    if let Err(err) = run(&args) {
+        eprintln!("sad: {}", err);
         process::exit(1);
-    });
-
-    let mut file = File::open(&opts.file)?;
-    // check for BOM
-    let mut buf = [0; 3];
-    file.read(&mut buf)?;
+    }
+}
 
-    if buf != "\u{feff}".as_bytes() {
-        file.seek(SeekFrom::Start(0))?;
+fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    // configure cli, command line options replace configured ones
+    let opts = Cli::with_config(args, Config::load()?)?;
+    match opts.command {
+        Command::Help => {
+            println!("{}", HELP);
+            return Ok(());
+        }
+        Command::Config => {
+            opts.config.write_tsv(BufWriter::new(stdout()))?;
+            return Ok(());
+        }
+        _ => {}
     }
-    // init storage
-    let project_id = opts.project;
-    let mut storage = Storage::new(vec![Box::new(move |p| {
-        if let Some(id) = &project_id {
-            return id == &p.id;
+
+    let (file, bom) = file::open(&opts.file)?;
+
+    match opts.command {
+        Command::Help | Command::Config => {}
+        Command::List => {
+            let mut storage = list(&opts, file)?;
+            output(&opts, &storage)?;
+            if opts.watch {
+                let mut watcher = Watcher::new(&opts.file);
+                loop {
+                    thread::sleep(watch::POLL_INTERVAL);
+                    if watcher.changed() && reload(&opts, &mut storage, list) {
+                        output(&opts, &storage)?;
+                    }
+                }
+            }
+        }
+        Command::Stats => {
+            let stats = Stats::new(&load(&opts, file)?);
+            write_table(&opts, &stats.table())?;
+        }
+        Command::Series => {
+            let storage = load(&opts, file)?;
+            let table = series(&storage, opts.period, opts.by.as_ref().map(|c| c.as_ref()))?;
+            write_table(&opts, &table)?;
+        }
+        Command::Pivot => {
+            let storage = load(&opts, file)?;
+            // options are checked by cli
+            let pivot = opts.pivot().ok_or("pivot requires --rows and --cols")?;
+            write_table(&opts, &pivot.table(&storage)?)?;
+        }
+        Command::Query => {
+            let query = Query::parse(opts.query.as_ref().map_or("", |q| q.as_ref()))?;
+            write_table(&opts, &query.run(&load(&opts, file)?)?)?;
+        }
+        Command::Serve => {
+            let mut storage = load(&opts, file)?;
+            let listener = TcpListener::bind(&opts.listen)?;
+            eprintln!(
+                "serving {} projects on http://{}",
+                storage.size(),
+                listener.local_addr()?
+            );
+            let mut watcher = Watcher::new(&opts.file);
+            server::serve(&listener, &mut storage, |storage| {
+                if opts.watch && watcher.changed() {
+                    reload(&opts, storage, load);
+                }
+            })?;
         }
-        true
-    })]);
-    // parse data into storage
-    parse_tsv(Box::new(BufReader::new(file)), &mut storage);
+        Command::Validate => {
+            let mut storage = Storage::new(vec![]);
+            let parse_opts = ParseOptions::default();
+            if let Err(errors) = try_parse_tsv(BufReader::new(file), &mut storage, parse_opts) {
+                write_output(&opts, |w| {
+                    for err in errors.iter() {
+                        writeln!(w, "{}: {}", opts.file.display(), err)?;
+                    }
+                    Ok(())
+                })?;
+                return Err(format!("{} invalid lines", errors.len()).into());
+            }
+            eprintln!("{} projects valid", storage.size());
+        }
+        Command::Convert => {
+            let mut storage = Storage::new(vec![opts.filter()]);
+            read(&opts, file, &mut storage, ParseOptions::default())?;
+            storage.paginate(opts.pagination()?);
+            output(&opts, &storage)?;
+        }
+        Command::Update | Command::Delete => {
+            // all projects, comments and blank lines are kept to be written back
+            let mut storage = Storage::new(vec![]);
+            let parse_opts = ParseOptions {
+                keep_blank_lines: true,
+            };
+            read_tsv(file, &mut storage, parse_opts)?;
+
+            let filter = opts.filter();
+            let n = if opts.command == Command::Update {
+                storage.update_all(&filter, &opts.assignments)?
+            } else {
+                storage.delete(&filter)
+            };
+
+            if opts.sort_by_start_date {
+                storage.sort_by_start_date();
+            }
+
+            file::write_atomic(&opts.file, bom, |w| write_source_tsv(w, &storage))?;
+            eprintln!("{} projects changed", n);
+        }
+
+        Command::Diff => {
+            let mut old = Storage::new(vec![opts.filter()]);
+            read(&opts, file, &mut old, ParseOptions::default())?;
+            let path = &opts.with[0];
+            let (file, _) = file::open(path)?;
+            let mut new = Storage::new(vec![opts.filter()]);
+            read_tsv(file, &mut new, ParseOptions::default())
+                .map_err(|err| format!("{}: {}", path.display(), err))?;
+
+            let key: Vec<&str> = opts.key.iter().map(|k| k.as_ref()).collect();
+            let d = diff::diff(&old, &new, &key)?;
+            write_output(&opts, |w| match opts.format {
+                Format::Json => diff::write_json(w, &d),
+                Format::Tsv | Format::Table => diff::write_tsv(w, &d),
+                Format::Markdown
+                | Format::Html
+                | Format::Sqlite
+                | Format::Parquet
+                | Format::Xlsx => Err(unsupported(opts.format)),
+            })?;
+        }
+        Command::Merge => {
+            let mut storage = Storage::new(vec![opts.filter()]);
+            read(&opts, file, &mut storage, ParseOptions::default())?;
+            for path in opts.with.iter() {
+                let (file, _) = file::open(path)?;
+                let mut other = Storage::new(vec![opts.filter()]);
+                read_tsv(file, &mut other, ParseOptions::default())
+                    .map_err(|err| format!("{}: {}", path.display(), err))?;
+                // conflicts are reported, merged projects are written to output
+                for c in storage.merge(&other, opts.on_conflict)? {
+                    eprintln!("{}: {}", path.display(), c);
+                }
+            }
+
+            if opts.sort_by_start_date {
+                storage.sort_by_start_date();
+            }
+
+            output(&opts, &storage)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Loader loads storage of input file
+type Loader = fn(&Cli, File) -> Result<Storage, Box<dyn Error>>;
+
+// load parses input file and adds joined and derived columns,
+// filters are applied after join and derive when they refer to added columns
+fn load(opts: &Cli, file: File) -> Result<Storage, Box<dyn Error>> {
+    let enrich = !opts.joins.is_empty() || !opts.derived.is_empty();
+    let filters = if enrich { vec![] } else { vec![opts.filter()] };
+    let mut storage = Storage::new(filters);
+    // blank lines are kept to be counted by stats
+    let parse_opts = ParseOptions {
+        keep_blank_lines: true,
+    };
+    read(opts, file, &mut storage, parse_opts)?;
+
+    if enrich {
+        join(opts, &mut storage)?;
+        derive(opts, &mut storage)?;
+        let filter = opts.filter();
+        storage.delete(|p| !filter(p));
+    }
+    Ok(storage)
+}
+
+// read reads projects of input file, tsv or table of sqlite database
+fn read(
+    opts: &Cli,
+    file: File,
+    storage: &mut Storage,
+    parse_opts: ParseOptions,
+) -> Result<(), Box<dyn Error>> {
+    if import_sqlite(opts, storage)? || import_parquet(opts, storage)? {
+        return Ok(());
+    }
+    read_tsv(file, storage, parse_opts)
+}
+
+// read_tsv reads projects of tsv file, errors of all invalid lines are returned
+fn read_tsv(
+    file: File,
+    storage: &mut Storage,
+    parse_opts: ParseOptions,
+) -> Result<(), Box<dyn Error>> {
+    try_parse_tsv(BufReader::new(file), storage, parse_opts).map_err(|errors| errors.join("\n"))?;
+    Ok(())
+}
+
+// import_sqlite reads projects of table if input file is sqlite database,
+// returns whether it is
+#[cfg(feature = "sqlite")]
+fn import_sqlite(opts: &Cli, storage: &mut Storage) -> Result<bool, Box<dyn Error>> {
+    if !sqlite::is_database(&opts.file) {
+        return Ok(false);
+    }
+    let conn = sqlite::Connection::open(&opts.file)?;
+    sqlite::import(&conn, &opts.table, storage)?;
+    Ok(true)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn import_sqlite(_: &Cli, _: &mut Storage) -> Result<bool, Box<dyn Error>> {
+    Ok(false)
+}
+
+// export_sqlite writes projects to table of output database,
+// the table is replaced in one transaction
+#[cfg(feature = "sqlite")]
+fn export_sqlite(opts: &Cli, storage: &Storage, columns: &[&str]) -> Result<(), Box<dyn Error>> {
+    let path = opts
+        .output
+        .as_ref()
+        .ok_or("sqlite format requires --output database")?;
+    let mut conn = sqlite::Connection::open(path)?;
+    sqlite::export(&mut conn, storage, columns, &opts.table, !opts.no_overwrite)?;
+    eprintln!(
+        "{} projects written to table {} of {}",
+        storage.size(),
+        opts.table,
+        path.display()
+    );
+    Ok(())
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn export_sqlite(_: &Cli, _: &Storage, _: &[&str]) -> Result<(), Box<dyn Error>> {
+    Err("sad is built without sqlite support".into())
+}
+
+// import_parquet reads projects if input file is parquet file,
+// returns whether it is
+#[cfg(feature = "parquet")]
+fn import_parquet(opts: &Cli, storage: &mut Storage) -> Result<bool, Box<dyn Error>> {
+    if !columnar::is_parquet(&opts.file) {
+        return Ok(false);
+    }
+    columnar::read_parquet(File::open(&opts.file)?, storage)?;
+    Ok(true)
+}
+
+#[cfg(not(feature = "parquet"))]
+fn import_parquet(_: &Cli, _: &mut Storage) -> Result<bool, Box<dyn Error>> {
+    Ok(false)
+}
 
+// write_parquet writes projects in parquet format, the file is built
+// in memory as parquet writer needs thread safe output
+#[cfg(feature = "parquet")]
+fn write_parquet(w: &mut dyn Write, storage: &Storage, columns: &[&str]) -> io::Result<()> {
+    let mut buf = vec![];
+    columnar::write_parquet(&mut buf, storage, columns).map_err(io::Error::other)?;
+    w.write_all(&buf)?;
+    w.flush()
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(_: &mut dyn Write, _: &Storage, _: &[&str]) -> io::Result<()> {
+    Err(io::Error::other("sad is built without parquet support"))
+}
+
+// write_xlsx writes projects as xlsx workbook
+#[cfg(feature = "xlsx")]
+fn write_xlsx(
+    w: &mut dyn Write,
+    storage: &Storage,
+    columns: &[&str],
+    summary_by: Option<&str>,
+) -> io::Result<()> {
+    xlsx::write_xlsx(&mut *w, storage, columns, summary_by).map_err(io::Error::other)?;
+    w.flush()
+}
+
+#[cfg(not(feature = "xlsx"))]
+fn write_xlsx(_: &mut dyn Write, _: &Storage, _: &[&str], _: Option<&str>) -> io::Result<()> {
+    Err(io::Error::other("sad is built without xlsx support"))
+}
+
+// list loads projects ranked and sorted as requested
+fn list(opts: &Cli, file: File) -> Result<Storage, Box<dyn Error>> {
+    let mut storage = load(opts, file)?;
+    if opts.is_ranked() {
+        storage.top(
+            opts.top,
+            opts.rank_by
+                .as_ref()
+                .map_or("Savings amount", |c| c.as_ref()),
+            opts.by.as_ref().map(|c| c.as_ref()),
+            opts.ranking.unwrap_or(Ranking::Standard),
+        )?;
+    }
     if opts.sort_by_start_date {
         storage.sort_by_start_date();
     }
+    if let Some(column) = &opts.sort_by {
+        storage.sort_by_column(column)?;
+    }
+    if let (Some(search), true) = (&opts.search, opts.relevance) {
+        storage.rank_relevance(search)?;
+    }
+    storage.paginate(opts.pagination()?);
+    Ok(storage)
+}
 
-    // output data
-    write_tsv(Box::new(BufWriter::new(stdout())), &storage);
+// reload replaces storage with input file loaded again if it is valid
+// and logs changes, returns whether storage was replaced
+fn reload(opts: &Cli, storage: &mut Storage, load: Loader) -> bool {
+    let reloaded = watch::reload(storage, || {
+        let (file, _) = file::open(&opts.file).map_err(|err| err.to_string())?;
+        load(opts, file).map_err(|err| err.to_string())
+    });
+    let now = Local::now().format("%Y-%m-%d %H:%M:%S");
+    match reloaded {
+        Ok(summary) => {
+            eprintln!("{} reloaded {}, {}", now, opts.file.display(), summary);
+            true
+        }
+        Err(err) => {
+            eprintln!(
+                "{} keeping previous data, {} is invalid:\n{}",
+                now,
+                opts.file.display(),
+                err
+            );
+            false
+        }
+    }
+}
+
+// output writes projects in chosen format,
+// only chosen columns if any
+fn output(opts: &Cli, storage: &Storage) -> Result<(), Box<dyn Error>> {
+    let columns: Vec<&str> = if opts.columns.is_empty() {
+        storage.get_columns()
+    } else {
+        let columns: Vec<&str> = opts.columns.iter().map(|c| c.as_ref()).collect();
+        if let Some(c) = columns.iter().find(|c| storage.column_type(c).is_none()) {
+            return Err(format!("unknown column {}", c).into());
+        }
+        columns
+    };
 
+    if opts.format == Format::Sqlite {
+        return export_sqlite(opts, storage, &columns);
+    }
+    let text_template = match &opts.template {
+        Some(path) => {
+            let text =
+                fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+            Some(template::Template::parse(&text)?)
+        }
+        None => None,
+    };
+    let write = |w: &mut dyn Write, storage: &Storage| {
+        if let Some(t) = &text_template {
+            return t.write(w, storage);
+        }
+        match opts.format {
+            Format::Tsv => write_tsv_columns(w, storage, &columns),
+            Format::Json => json::write_projects(w, storage, &columns),
+            Format::Table => {
+                let mut table = Table::from_projects(storage.iter(), &columns);
+                if let (Some(search), true) = (&opts.search, opts.relevance) {
+                    table.highlight(|column, value| search.highlight(column, value));
+                }
+                table.write_text(w)
+            }
+            Format::Markdown => Table::from_projects(storage.iter(), &columns).write_markdown(w),
+            Format::Html => report::write_html(w, storage, &columns),
+            Format::Parquet => write_parquet(w, storage, &columns),
+            Format::Xlsx => write_xlsx(w, storage, &columns, opts.summary_by.as_deref()),
+            Format::Sqlite => Err(unsupported(opts.format)),
+        }
+    };
+
+    let template = match &opts.split {
+        Some(template) => Template::parse(template)?,
+        None => return write_output(opts, |w| write(w, storage)),
+    };
+    let parts = partition(storage, &template)?;
+    // no file is written if any of them exists
+    if let Some(path) = parts.keys().find(|p| opts.no_overwrite && p.exists()) {
+        return Err(format!("{} already exists", path.display()).into());
+    }
+    for (path, part) in parts.iter() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        if opts.no_overwrite {
+            file::write_atomic_new(path, false, |w| write(w, part))?;
+        } else {
+            file::write_atomic(path, false, |w| write(w, part))?;
+        }
+    }
+    eprintln!("{} files written", parts.len());
+    Ok(())
+}
+
+// write_table writes report table in chosen format
+fn write_table(opts: &Cli, table: &Table) -> Result<(), Box<dyn Error>> {
+    write_output(opts, |w| match opts.format {
+        Format::Tsv => table.write_tsv(w),
+        Format::Json => table.write_json(w),
+        Format::Table => table.write_text(w),
+        Format::Markdown => table.write_markdown(w),
+        Format::Sqlite | Format::Parquet | Format::Xlsx | Format::Html => {
+            Err(unsupported(opts.format))
+        }
+    })
+}
+
+// unsupported is error of writing projects only format as report
+fn unsupported(format: Format) -> io::Error {
+    io::Error::other(format!(
+        "{} format is supported by list and convert only",
+        format
+    ))
+}
+
+// write_output writes to output file atomically if set, to stdout otherwise
+fn write_output<F>(opts: &Cli, write: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(&mut dyn Write) -> io::Result<()>,
+{
+    match &opts.output {
+        Some(path) if opts.no_overwrite => file::write_atomic_new(path, false, |w| write(w))?,
+        Some(path) => file::write_atomic(path, false, |w| write(w))?,
+        None => {
+            let mut writer = BufWriter::new(stdout());
+            write(&mut writer)?;
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+// join adds columns of lookup files to storage, unmatched projects are reported
+fn join(opts: &Cli, storage: &mut Storage) -> Result<(), Box<dyn Error>> {
+    for (path, on) in opts.joins.iter() {
+        let (file, _) = file::open(path)?;
+        let lookup = parse_lookup_tsv(BufReader::new(file), on.as_ref().map(|s| s.as_ref()))?;
+        for p in storage.join(&lookup, &lookup.key, opts.join_kind)? {
+            eprintln!(
+                "{}: no match for project {} by {} {:?}",
+                path.display(),
+                p.id,
+                lookup.key,
+                p.get(&lookup.key).unwrap_or_default()
+            );
+        }
+    }
+    Ok(())
+}
+
+// derive adds derived columns to storage in order of definition
+fn derive(opts: &Cli, storage: &mut Storage) -> Result<(), Box<dyn Error>> {
+    let now = Local::now().naive_local();
+    for d in opts.derived.iter() {
+        storage.derive(&Derived::parse(d)?, now)?;
+    }
     Ok(())
 }
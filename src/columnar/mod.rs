@@ -0,0 +1,336 @@
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Decimal128Type, Float64Type, Int32Type, TimestampMillisecondType};
+use arrow_array::{
+    Array, ArrayRef, Decimal128Array, DictionaryArray, Float64Array, RecordBatch,
+    RecordBatchReader, StringArray, TimestampMillisecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::DateTime;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use project::{Project, Type, Value, COLUMN_NAMES, NULL_STR};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use storage::Storage;
+
+// number of projects in record batch
+pub const BATCH_SIZE: usize = 8192;
+
+// first bytes of every parquet file
+const MAGIC: &[u8] = b"PAR1";
+
+// savings amounts are decimals with 6 digits of fraction
+const PRECISION: u8 = 18;
+const SCALE: i8 = 6;
+
+// is_parquet reports whether file at path is parquet file
+pub fn is_parquet(path: &Path) -> bool {
+    let mut buf = [0; 4];
+    match File::open(path) {
+        Ok(mut file) => file.read_exact(&mut buf).is_ok() && buf == MAGIC,
+        Err(_) => false,
+    }
+}
+
+// schema returns arrow schema of columns, start date is timestamp,
+// savings amount is decimal and category, currency and complexity
+// are dictionary encoded, added columns are nullable
+pub fn schema(storage: &Storage, columns: &[&str]) -> Result<Schema, String> {
+    let dictionary = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+    let timestamp = DataType::Timestamp(TimeUnit::Millisecond, None);
+    let mut fields = vec![];
+    for c in columns.iter() {
+        let (t, nullable) = match *c {
+            "Start date" => (timestamp.clone(), false),
+            "Category" | "Complexity" => (dictionary.clone(), false),
+            "Currency" => (dictionary.clone(), true),
+            "Savings amount" => (DataType::Decimal128(PRECISION, SCALE), true),
+            c if COLUMN_NAMES.contains(&c) => (DataType::Utf8, false),
+            c => match storage.column_type(c) {
+                Some(Type::Str) => (DataType::Utf8, true),
+                Some(Type::Number) => (DataType::Float64, true),
+                Some(Type::Date) => (timestamp.clone(), true),
+                None => return Err(format!("unknown column {}", c)),
+            },
+        };
+        fields.push(Field::new(*c, t, nullable));
+    }
+    Ok(Schema::new(fields))
+}
+
+// batches converts columns of projects to record batches of BATCH_SIZE projects
+pub fn batches(storage: &Storage, columns: &[&str]) -> Result<Vec<RecordBatch>, String> {
+    let schema = Arc::new(schema(storage, columns)?);
+    let projects: Vec<&Project> = storage.iter().collect();
+    let mut batches = vec![];
+    for chunk in projects.chunks(BATCH_SIZE) {
+        let arrays = schema
+            .fields()
+            .iter()
+            .map(|f| array(chunk, f))
+            .collect::<Result<Vec<ArrayRef>, String>>()?;
+        batches.push(RecordBatch::try_new(schema.clone(), arrays).map_err(|e| e.to_string())?);
+    }
+    Ok(batches)
+}
+
+// write_parquet writes columns of projects as snappy compressed parquet
+pub fn write_parquet<W: Write + Send>(
+    writer: W,
+    storage: &Storage,
+    columns: &[&str],
+) -> Result<(), String> {
+    let schema = Arc::new(schema(storage, columns)?);
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer =
+        ArrowWriter::try_new(writer, schema, Some(props)).map_err(|e| e.to_string())?;
+    for batch in batches(storage, columns)? {
+        writer.write(&batch).map_err(|e| e.to_string())?;
+    }
+    writer.close().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// read_parquet reads projects of parquet file into storage, file must have
+// all input columns, other columns are added by their arrow types
+pub fn read_parquet(file: File, storage: &mut Storage) -> Result<(), String> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .and_then(|b| b.build())
+        .map_err(|e| e.to_string())?;
+    let schema = reader.schema();
+    for c in COLUMN_NAMES.iter() {
+        if schema.column_with_name(c).is_none() {
+            return Err(format!("parquet file misses column {}", c));
+        }
+    }
+    let mut extra = vec![];
+    for f in schema.fields().iter() {
+        if COLUMN_NAMES.contains(&f.name().as_str()) {
+            continue;
+        }
+        let t = match f.data_type() {
+            DataType::Float64 | DataType::Decimal128(..) => Type::Number,
+            DataType::Timestamp(TimeUnit::Millisecond, _) => Type::Date,
+            _ => Type::Str,
+        };
+        storage.add_column(f.name(), t)?;
+        extra.push(f.name().clone());
+    }
+
+    let mut n = 0;
+    for batch in reader {
+        let batch = batch.map_err(|e| e.to_string())?;
+        let column = |c: &str| batch.column_by_name(c).unwrap();
+        for i in 0..batch.num_rows() {
+            n += 1;
+            let mut record = vec![];
+            for c in COLUMN_NAMES.iter() {
+                record.push(match value(column(c), i) {
+                    Ok(Value::Null) => NULL_STR.to_string(),
+                    Ok(v) => v.to_string(),
+                    Err(err) => return Err(format!("row {}: {} {}", n, c, err)),
+                });
+            }
+            let mut p = Project::new(record.iter().map(|v| v.as_str()).collect())
+                .map_err(|err| format!("row {}: {}", n, err))?;
+            for c in extra.iter() {
+                let v = value(column(c), i).map_err(|err| format!("row {}: {} {}", n, c, err))?;
+                p.extra.insert(c.clone(), v);
+            }
+            storage.add(p);
+        }
+    }
+    Ok(())
+}
+
+// array returns values of field column of projects as arrow array
+fn array(projects: &[&Project], field: &Field) -> Result<ArrayRef, String> {
+    let values: Vec<Value> = projects
+        .iter()
+        .map(|p| p.value_of(field.name()).unwrap_or(Value::Null))
+        .collect();
+    let strings = || -> Vec<Option<String>> {
+        values
+            .iter()
+            .map(|v| match v {
+                Value::Null => None,
+                v => Some(v.to_string()),
+            })
+            .collect()
+    };
+    let numbers = || -> Vec<Option<f64>> {
+        values
+            .iter()
+            .map(|v| match v {
+                Value::Number(n) => Some(*n),
+                _ => None,
+            })
+            .collect()
+    };
+
+    let array: ArrayRef = match field.data_type() {
+        DataType::Utf8 => Arc::new(StringArray::from(strings())),
+        DataType::Dictionary(..) => {
+            let strings = strings();
+            Arc::new(
+                strings
+                    .iter()
+                    .map(|s| s.as_deref())
+                    .collect::<DictionaryArray<Int32Type>>(),
+            )
+        }
+        DataType::Timestamp(..) => Arc::new(TimestampMillisecondArray::from(
+            values
+                .iter()
+                .map(|v| match v {
+                    Value::Date(d) => Some(d.and_utc().timestamp_millis()),
+                    _ => None,
+                })
+                .collect::<Vec<Option<i64>>>(),
+        )),
+        DataType::Decimal128(precision, scale) => {
+            let m = 10f64.powi(i32::from(*scale));
+            // largest number of units of decimal, exclusive
+            let max = 10f64.powi(i32::from(*precision));
+            let decimals = numbers()
+                .into_iter()
+                .zip(projects.iter())
+                .map(|(n, p)| match n.map(|n| (n * m).round()) {
+                    Some(units) if units.is_nan() || units.abs() >= max => Err(format!(
+                        "{} {} is out of range of decimal({}, {})",
+                        field.name(),
+                        p.get_source(field.name()).unwrap_or_default(),
+                        precision,
+                        scale
+                    )),
+                    units => Ok(units.map(|units| units as i128)),
+                })
+                .collect::<Result<Vec<Option<i128>>, String>>()?;
+            Arc::new(
+                Decimal128Array::from(decimals)
+                    .with_precision_and_scale(*precision, *scale)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        DataType::Float64 => Arc::new(Float64Array::from(numbers())),
+        t => return Err(format!("unsupported data type {}", t)),
+    };
+    Ok(array)
+}
+
+// value returns typed value of row i of arrow array
+fn value(array: &ArrayRef, i: usize) -> Result<Value, String> {
+    if array.is_null(i) {
+        return Ok(Value::Null);
+    }
+    let v = match array.data_type() {
+        DataType::Utf8 => Value::Str(array.as_string::<i32>().value(i).to_string()),
+        DataType::Dictionary(key, values)
+            if **key == DataType::Int32 && **values == DataType::Utf8 =>
+        {
+            let dict = array.as_dictionary::<Int32Type>();
+            let key = dict.keys().value(i) as usize;
+            Value::Str(dict.values().as_string::<i32>().value(key).to_string())
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            let ms = array.as_primitive::<TimestampMillisecondType>().value(i);
+            match DateTime::from_timestamp_millis(ms) {
+                Some(d) => Value::Date(d.naive_utc()),
+                None => return Err(format!("timestamp out of range {}", ms)),
+            }
+        }
+        DataType::Decimal128(_, scale) => {
+            let n = array.as_primitive::<Decimal128Type>().value(i);
+            Value::Number(n as f64 / 10f64.powi(i32::from(*scale)))
+        }
+        DataType::Float64 => Value::Number(array.as_primitive::<Float64Type>().value(i)),
+        t => return Err(format!("unsupported data type {}", t)),
+    };
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expr::Derived;
+    use project::DATE_FORMAT;
+    use test_util::*;
+
+    #[test]
+    fn test_parquet() {
+        let mut storage = Storage::new(vec![]);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+        let d = Derived::parse("Start = Start date + 1").unwrap();
+        storage.derive(&d, get_project(1).start_date).unwrap();
+        let columns = storage.get_columns();
+
+        let batches = batches(&storage, &columns).unwrap();
+        assert_eq!(batches.len(), 1);
+        let schema = batches[0].schema();
+        assert_eq!(
+            schema.field_with_name("Category").unwrap().data_type(),
+            &DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8))
+        );
+        assert_eq!(
+            schema
+                .field_with_name("Savings amount")
+                .unwrap()
+                .data_type(),
+            &DataType::Decimal128(18, 6)
+        );
+        let amounts = batches[0]
+            .column_by_name("Savings amount")
+            .unwrap()
+            .as_primitive::<Decimal128Type>();
+        assert!(amounts.is_null(0));
+        assert_eq!(amounts.value(1), 141415942696);
+
+        let path = std::env::temp_dir().join(format!("sad_parquet_{}.parquet", std::process::id()));
+        write_parquet(File::create(&path).unwrap(), &storage, &columns).unwrap();
+        assert!(is_parquet(&path));
+
+        let mut read = Storage::new(vec![]);
+        read_parquet(File::open(&path).unwrap(), &mut read).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.get_columns(), columns);
+        assert_eq!(read.column_type("Start"), Some(Type::Date));
+        assert_eq!(
+            read.iter().collect::<Vec<&Project>>(),
+            storage.iter().collect::<Vec<&Project>>()
+        );
+        assert_eq!(
+            read.iter().next().unwrap().get("Start date").unwrap(),
+            format!("{}", get_project(1).start_date.format(DATE_FORMAT))
+        );
+    }
+
+    #[test]
+    fn test_parquet_out_of_range() {
+        let mut storage = Storage::new(vec![]);
+        storage.insert(get_record(2)).unwrap();
+        let columns = COLUMN_NAMES.to_vec();
+        for amount in ["999999999999.99", "-999999999999.99"] {
+            storage.update(|_p| true, "Savings amount", amount).unwrap();
+            assert!(batches(&storage, &columns).is_ok());
+        }
+
+        for amount in ["12345678901234.5", "1e300", "-1e13", "inf", "NaN"] {
+            storage.update(|_p| true, "Savings amount", amount).unwrap();
+            assert!(write_parquet(vec![], &storage, &columns).is_err());
+        }
+        storage
+            .update(|_p| true, "Savings amount", "12345678901234.5")
+            .unwrap();
+        assert_eq!(
+            batches(&storage, &columns).unwrap_err(),
+            "Savings amount 12345678901234.5 is out of range of decimal(18, 6)"
+        );
+    }
+}
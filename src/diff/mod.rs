@@ -0,0 +1,273 @@
+use json;
+use project::{Project, COLUMN_NAMES};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use storage::Storage;
+
+const SEP: &str = "\t";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub column: String,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change<'a> {
+    Added(&'a Project),
+    Removed(&'a Project),
+    Changed {
+        old: &'a Project,
+        new: &'a Project,
+        fields: Vec<FieldChange>,
+    },
+}
+
+// Diff lists changes between two storages, projects are matched by key columns,
+// projects with the same key are matched in order of appearance
+pub struct Diff<'a> {
+    pub key: Vec<String>,
+    pub changes: Vec<Change<'a>>,
+}
+
+// diff compares projects of old and new storages by key columns,
+// added and changed projects are in order of new storage,
+// removed projects follow in order of old storage
+pub fn diff<'a>(old: &'a Storage, new: &'a Storage, key: &[&str]) -> Result<Diff<'a>, String> {
+    if key.is_empty() {
+        return Err("diff key is empty".to_string());
+    }
+    for k in key {
+        if !COLUMN_NAMES.contains(k) {
+            return Err(format!("unknown key column {}", k));
+        }
+    }
+
+    let key_of = |p: &Project| -> Vec<String> { key.iter().map(|k| p.get(k).unwrap()).collect() };
+
+    let mut olds: HashMap<Vec<String>, Vec<(usize, &Project)>> = HashMap::new();
+    for (i, p) in old.iter().enumerate().rev() {
+        olds.entry(key_of(p)).or_default().push((i, p));
+    }
+
+    let mut matched = vec![false; old.size()];
+    let mut changes = vec![];
+    for p in new.iter() {
+        match olds.get_mut(&key_of(p)).and_then(|v| v.pop()) {
+            Some((i, o)) => {
+                matched[i] = true;
+                let fields = changed_fields(o, p);
+                if !fields.is_empty() {
+                    changes.push(Change::Changed {
+                        old: o,
+                        new: p,
+                        fields,
+                    });
+                }
+            }
+            None => changes.push(Change::Added(p)),
+        }
+    }
+    for (i, p) in old.iter().enumerate() {
+        if !matched[i] {
+            changes.push(Change::Removed(p));
+        }
+    }
+
+    Ok(Diff {
+        key: key.iter().map(|k| k.to_string()).collect(),
+        changes,
+    })
+}
+
+// changed_fields returns columns with different values in old and new,
+// values are compared by type, not by how they are printed
+pub fn changed_fields(old: &Project, new: &Project) -> Vec<FieldChange> {
+    COLUMN_NAMES
+        .iter()
+        .filter_map(|c| {
+            if old.value_of(c) == new.value_of(c) {
+                return None;
+            }
+            Some(FieldChange {
+                column: c.to_string(),
+                old: old.get(c).unwrap(),
+                new: new.get(c).unwrap(),
+            })
+        })
+        .collect()
+}
+
+impl<'a> Change<'a> {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Change::Added(_) => "added",
+            Change::Removed(_) => "removed",
+            Change::Changed { .. } => "changed",
+        }
+    }
+
+    pub fn project(&self) -> &'a Project {
+        match *self {
+            Change::Added(p) | Change::Removed(p) => p,
+            Change::Changed { new, .. } => new,
+        }
+    }
+}
+
+// write_tsv writes one line per added or removed project
+// and one line per changed field
+pub fn write_tsv<W: Write>(mut writer: W, diff: &Diff) -> io::Result<()> {
+    let mut header = vec!["Change"];
+    header.extend(diff.key.iter().map(|k| k.as_str()));
+    header.extend(&["Column", "Old value", "New value"]);
+    writer.write_all(header.join(SEP).as_bytes())?;
+    writer.write_all(b"\n")?;
+
+    for change in diff.changes.iter() {
+        let p = change.project();
+        let mut line: Vec<String> = vec![change.kind().to_string()];
+        line.extend(diff.key.iter().map(|k| p.get(k).unwrap()));
+        match change {
+            Change::Changed { fields, .. } => {
+                for f in fields {
+                    let mut line = line.clone();
+                    line.extend(vec![f.column.clone(), f.old.clone(), f.new.clone()]);
+                    writer.write_all(line.join(SEP).as_bytes())?;
+                    writer.write_all(b"\n")?;
+                }
+            }
+            _ => {
+                line.extend(vec![String::new(); 3]);
+                writer.write_all(line.join(SEP).as_bytes())?;
+                writer.write_all(b"\n")?;
+            }
+        }
+    }
+
+    writer.flush()
+}
+
+// write_json writes diff as json object with added, removed and changed lists
+pub fn write_json<W: Write>(mut writer: W, diff: &Diff) -> io::Result<()> {
+    let mut added = vec![];
+    let mut removed = vec![];
+    let mut changed = vec![];
+    for change in diff.changes.iter() {
+        match change {
+            Change::Added(p) => added.push(json::project(p, &COLUMN_NAMES)),
+            Change::Removed(p) => removed.push(json::project(p, &COLUMN_NAMES)),
+            Change::Changed { new, fields, .. } => {
+                let key: Vec<(&str, String)> = diff
+                    .key
+                    .iter()
+                    .map(|k| (k.as_str(), json::quote(&new.get(k).unwrap())))
+                    .collect();
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|f| {
+                        json::object(&[
+                            ("column", json::quote(&f.column)),
+                            ("old", json::quote(&f.old)),
+                            ("new", json::quote(&f.new)),
+                        ])
+                    })
+                    .collect();
+                changed.push(json::object(&[
+                    ("key", json::object(&key)),
+                    ("fields", json::array(&fields)),
+                ]));
+            }
+        }
+    }
+
+    let out = json::object(&[
+        ("added", json::array(&added)),
+        ("removed", json::array(&removed)),
+        ("changed", json::array(&changed)),
+    ]);
+    writer.write_all(out.as_bytes())?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str;
+    use test_util::*;
+
+    #[test]
+    fn test_diff() {
+        let mut changed = get_record(2);
+        changed[4] = "Clark Kent";
+        let mut added = get_record(1);
+        added[0] = "3";
+        let old = get_storage(vec![get_record(1), get_record(2)]);
+        let new = get_storage(vec![changed, added]);
+
+        let d = diff(&old, &new, &["Project"]).unwrap();
+        assert_eq!(
+            d.changes
+                .iter()
+                .map(|c| (c.kind(), c.project().id.as_str()))
+                .collect::<Vec<(&str, &str)>>(),
+            vec![("changed", "2"), ("added", "3"), ("removed", "1")]
+        );
+
+        let mut buf: Vec<u8> = Vec::new();
+        write_tsv(&mut buf, &d).unwrap();
+        assert_eq!(
+            str::from_utf8(&buf).unwrap(),
+            "Change\tProject\tColumn\tOld value\tNew value
+changed\t2\tResponsible\tDaisy Milks\tClark Kent
+added\t3\t\t\t
+removed\t1\t\t\t
+"
+        );
+
+        let mut buf: Vec<u8> = Vec::new();
+        write_json(&mut buf, &d).unwrap();
+        let out = str::from_utf8(&buf).unwrap();
+        assert!(out.starts_with(r#"{"added":[{"Project":"3","#));
+        assert!(out.ends_with(
+            r#""changed":[{"key":{"Project":"2"},"fields":[{"column":"Responsible","old":"Daisy Milks","new":"Clark Kent"}]}]}
+"#
+        ));
+
+        assert!(diff(&old, &new, &["Unknown"]).is_err());
+        assert!(diff(&old, &old, &["Category"]).unwrap().changes.is_empty());
+    }
+
+    #[test]
+    fn test_diff_typed_values() {
+        let mut old = get_record(1);
+        old[5] = "1000.0000001";
+        let mut new = get_record(1);
+        new[5] = "1000.0000002";
+        let old = get_storage(vec![old]);
+        let new = get_storage(vec![new]);
+
+        // values printed the same are still different
+        let d = diff(&old, &new, &["Project"]).unwrap();
+        assert_eq!(d.changes.len(), 1);
+        assert!(diff(&old, &old, &["Project"]).unwrap().changes.is_empty());
+    }
+
+    #[test]
+    fn test_diff_duplicate_keys() {
+        let old = get_storage(vec![get_record(1), get_record(2)]);
+        let new = get_storage(vec![get_record(2), get_record(1), get_record(2)]);
+
+        let d = diff(&old, &new, &["Category", "Responsible"]).unwrap();
+        // projects with the same key are matched in order
+        assert_eq!(
+            d.changes
+                .iter()
+                .map(|c| (c.kind(), c.project().id.as_str()))
+                .collect::<Vec<(&str, &str)>>(),
+            vec![("changed", "2"), ("changed", "1"), ("added", "2")]
+        );
+    }
+}
@@ -0,0 +1,438 @@
+use chrono::{Datelike, Duration, NaiveDateTime};
+use project::{Project, Type, Value};
+use std::iter::Peekable;
+use std::str::Chars;
+
+// Expr is an expression over project columns, e.g.
+// `year(Start date)`, `Savings amount / 1000` or `today - Start date`.
+// Column names are written as is or in double quotes,
+// string literals are in single quotes
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Column(String),
+    Number(f64),
+    Str(String),
+    Today,
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+// Derived is a column computed from other columns of project
+#[derive(Debug, Clone, PartialEq)]
+pub struct Derived {
+    pub name: String,
+    pub expr: Expr,
+}
+
+impl Derived {
+    // parse parses derived column definition `<name> = <expression>`
+    pub fn parse(s: &str) -> Result<Derived, String> {
+        let mut parts = s.splitn(2, '=');
+        match (parts.next().map(|n| n.trim()), parts.next()) {
+            (Some(name), Some(expr)) if !name.is_empty() => Ok(Derived {
+                name: name.to_string(),
+                expr: Expr::parse(expr)?,
+            }),
+            _ => Err(format!(
+                "derived column expects <name> = <expression>: {}",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Str(String),
+    Number(f64),
+    Op(char),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' => {
+                chars.next();
+            }
+            '+' | '-' | '*' | '/' | '(' | ')' | ',' => {
+                tokens.push(Token::Op(c));
+                chars.next();
+            }
+            '"' | '\'' => {
+                chars.next();
+                let v = read_until(&mut chars, c)?;
+                tokens.push(if c == '"' {
+                    Token::Quoted(v)
+                } else {
+                    Token::Str(v)
+                });
+            }
+            '0'..='9' | '.' => {
+                let mut v = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_digit() || c == '.') {
+                        break;
+                    }
+                    v.push(c);
+                    chars.next();
+                }
+                match v.parse() {
+                    Ok(n) => tokens.push(Token::Number(n)),
+                    Err(_) => return Err(format!("invalid number {}", v)),
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut v = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    v.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(v));
+            }
+            c => return Err(format!("unexpected character {:?}", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+fn read_until(chars: &mut Peekable<Chars>, end: char) -> Result<String, String> {
+    let mut v = String::new();
+    for c in chars {
+        if c == end {
+            return Ok(v);
+        }
+        v.push(c);
+    }
+    Err(format!("missing closing {}", end))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn eat(&mut self, op: char) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut left = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op(c)) if *c == '+' || *c == '-' => *c,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut left = self.factor()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op(c)) if *c == '*' || *c == '/' => *c,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.factor()?));
+        }
+    }
+
+    fn factor(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Op('-')) => Ok(Expr::Neg(Box::new(self.factor()?))),
+            Some(Token::Op('(')) => {
+                let e = self.expr()?;
+                if !self.eat(')') {
+                    return Err("missing closing )".to_string());
+                }
+                Ok(e)
+            }
+            Some(Token::Number(n)) => Ok(Expr::Number(n)),
+            Some(Token::Str(s)) => Ok(Expr::Str(s)),
+            Some(Token::Quoted(s)) => Ok(Expr::Column(s)),
+            Some(Token::Word(w)) => {
+                if self.eat('(') {
+                    return self.call(w);
+                }
+                // consecutive words are a column name with spaces
+                let mut name = w;
+                while let Some(Token::Word(w)) = self.peek().cloned() {
+                    name.push(' ');
+                    name.push_str(&w);
+                    self.pos += 1;
+                }
+                if name == "today" {
+                    return Ok(Expr::Today);
+                }
+                Ok(Expr::Column(name))
+            }
+            Some(t) => Err(format!("unexpected {:?}", t)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+
+    fn call(&mut self, name: String) -> Result<Expr, String> {
+        let mut args = vec![];
+        if !self.eat(')') {
+            loop {
+                args.push(self.expr()?);
+                if self.eat(')') {
+                    break;
+                }
+                if !self.eat(',') {
+                    return Err(format!("missing closing ) of {}", name));
+                }
+            }
+        }
+        if name == "today" && args.is_empty() {
+            return Ok(Expr::Today);
+        }
+        Ok(Expr::Call(name, args))
+    }
+}
+
+impl Expr {
+    pub fn parse(s: &str) -> Result<Expr, String> {
+        let mut p = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        let e = p.expr()?;
+        if let Some(t) = p.peek() {
+            return Err(format!("unexpected {:?}", t));
+        }
+        Ok(e)
+    }
+
+    // check returns type of expression with types of columns from schema
+    // or error if expression is not valid for the schema
+    pub fn check<F: Fn(&str) -> Option<Type>>(&self, schema: &F) -> Result<Type, String> {
+        match self {
+            Expr::Column(c) => schema(c).ok_or_else(|| format!("unknown column {}", c)),
+            Expr::Number(_) => Ok(Type::Number),
+            Expr::Str(_) => Ok(Type::Str),
+            Expr::Today => Ok(Type::Date),
+            Expr::Neg(e) => match e.check(schema)? {
+                Type::Number => Ok(Type::Number),
+                t => Err(format!("can't negate {}", t)),
+            },
+            Expr::Binary(op, l, r) => binary_type(*op, l.check(schema)?, r.check(schema)?),
+            Expr::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|a| a.check(schema))
+                    .collect::<Result<Vec<Type>, String>>()?;
+                call_type(name, &args)
+            }
+        }
+    }
+
+    // eval evaluates expression for project, missing values and
+    // invalid operations like division by zero give Null
+    pub fn eval(&self, p: &Project, now: NaiveDateTime) -> Value {
+        match self {
+            Expr::Column(c) => p.value_of(c).unwrap_or(Value::Null),
+            Expr::Number(n) => Value::Number(*n),
+            Expr::Str(s) => Value::Str(s.clone()),
+            Expr::Today => Value::Date(now.date().and_hms_opt(0, 0, 0).unwrap()),
+            Expr::Neg(e) => match e.eval(p, now) {
+                Value::Number(n) => Value::Number(-n),
+                _ => Value::Null,
+            },
+            Expr::Binary(op, l, r) => binary(*op, l.eval(p, now), r.eval(p, now)),
+            Expr::Call(name, args) => {
+                let args: Vec<Value> = args.iter().map(|a| a.eval(p, now)).collect();
+                call(name, &args)
+            }
+        }
+    }
+}
+
+// binary_type returns type of arithmetic operation on types
+pub fn binary_type(op: char, l: Type, r: Type) -> Result<Type, String> {
+    match (op, l, r) {
+        (_, Type::Number, Type::Number) => Ok(Type::Number),
+        ('+', Type::Str, Type::Str) => Ok(Type::Str),
+        ('+', Type::Date, Type::Number) | ('-', Type::Date, Type::Number) => Ok(Type::Date),
+        ('-', Type::Date, Type::Date) => Ok(Type::Number),
+        _ => Err(format!("can't apply {} to {} and {}", op, l, r)),
+    }
+}
+
+// binary applies arithmetic operation, invalid operations and dates
+// out of range give Null
+pub fn binary(op: char, a: Value, b: Value) -> Value {
+    match (op, a, b) {
+        ('+', Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+        ('-', Value::Number(a), Value::Number(b)) => Value::Number(a - b),
+        ('*', Value::Number(a), Value::Number(b)) => Value::Number(a * b),
+        ('/', Value::Number(a), Value::Number(b)) if b != 0.0 => Value::Number(a / b),
+        ('+', Value::Str(a), Value::Str(b)) => Value::Str(a + &b),
+        ('+', Value::Date(a), Value::Number(b)) => Duration::try_days(b as i64)
+            .and_then(|d| a.checked_add_signed(d))
+            .map_or(Value::Null, Value::Date),
+        ('-', Value::Date(a), Value::Number(b)) => Duration::try_days(b as i64)
+            .and_then(|d| a.checked_sub_signed(d))
+            .map_or(Value::Null, Value::Date),
+        ('-', Value::Date(a), Value::Date(b)) => {
+            Value::Number(a.date().signed_duration_since(b.date()).num_days() as f64)
+        }
+        _ => Value::Null,
+    }
+}
+
+// call_type returns result type of function called with argument types
+pub fn call_type(name: &str, args: &[Type]) -> Result<Type, String> {
+    match (name, args) {
+        ("year", [Type::Date])
+        | ("quarter", [Type::Date])
+        | ("month", [Type::Date])
+        | ("day", [Type::Date]) => Ok(Type::Number),
+        ("round", [Type::Number])
+        | ("round", [Type::Number, Type::Number])
+        | ("abs", [Type::Number]) => Ok(Type::Number),
+        ("lower", [Type::Str]) | ("upper", [Type::Str]) => Ok(Type::Str),
+        _ => {
+            let args: Vec<String> = args.iter().map(|t| t.to_string()).collect();
+            Err(format!("unknown function {}({})", name, args.join(", ")))
+        }
+    }
+}
+
+// call calls function, missing values give Null
+pub fn call(name: &str, args: &[Value]) -> Value {
+    match (name, args) {
+        ("year", [Value::Date(d)]) => Value::Number(d.year() as f64),
+        ("quarter", [Value::Date(d)]) => Value::Number((d.month0() / 3 + 1) as f64),
+        ("month", [Value::Date(d)]) => Value::Number(d.month() as f64),
+        ("day", [Value::Date(d)]) => Value::Number(d.day() as f64),
+        ("round", [Value::Number(n)]) => Value::Number(n.round()),
+        ("round", [Value::Number(n), Value::Number(digits)]) => {
+            let m = 10f64.powi(*digits as i32);
+            Value::Number((n * m).round() / m)
+        }
+        ("abs", [Value::Number(n)]) => Value::Number(n.abs()),
+        ("lower", [Value::Str(s)]) => Value::Str(s.to_lowercase()),
+        ("upper", [Value::Str(s)]) => Value::Str(s.to_uppercase()),
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use project::column_type;
+    use test_util::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Expr::parse("Savings amount / 1000").unwrap(),
+            Expr::Binary(
+                '/',
+                Box::new(Expr::Column("Savings amount".to_string())),
+                Box::new(Expr::Number(1000.0))
+            )
+        );
+        assert_eq!(
+            Expr::parse("today - \"Start date\"").unwrap(),
+            Expr::Binary(
+                '-',
+                Box::new(Expr::Today),
+                Box::new(Expr::Column("Start date".to_string()))
+            )
+        );
+        assert_eq!(
+            Derived::parse("Start year = year(Start date)").unwrap(),
+            Derived {
+                name: "Start year".to_string(),
+                expr: Expr::Call(
+                    "year".to_string(),
+                    vec![Expr::Column("Start date".to_string())]
+                ),
+            }
+        );
+        assert!(Expr::parse("(1 + 2").is_err());
+        assert!(Expr::parse("1 2").is_err());
+        assert!(Expr::parse("'abc").is_err());
+        assert!(Derived::parse("year(Start date)").is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        struct Data(&'static str, Result<Type, ()>);
+        let cases = vec![
+            Data("year(Start date)", Ok(Type::Number)),
+            Data("round(Savings amount / 1000, 2)", Ok(Type::Number)),
+            Data("today - Start date", Ok(Type::Number)),
+            Data("Start date + 7", Ok(Type::Date)),
+            Data("upper(Category) + ' ' + Responsible", Ok(Type::Str)),
+            Data("year(Category)", Err(())),
+            Data("Category * 2", Err(())),
+            Data("Unknown + 1", Err(())),
+            Data("-Category", Err(())),
+        ];
+
+        for Data(input, expected) in cases {
+            let got = Expr::parse(input).unwrap().check(&column_type);
+            assert_eq!(got.map_err(|_| ()), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval() {
+        let now = NaiveDate::from_ymd_opt(2014, 1, 31)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        struct Data(&'static str, usize, Value);
+        let cases = vec![
+            Data("year(Start date)", 2, Value::Number(2013.0)),
+            Data("quarter(Start date + 100)", 2, Value::Number(2.0)),
+            Data("Savings amount / 1000", 1, Value::Null),
+            Data("round(Savings amount / 1000, 1)", 2, Value::Number(141.4)),
+            Data("today - Start date", 1, Value::Number(30.0)),
+            Data("-(1 + 2) * 3", 1, Value::Number(-9.0)),
+            Data("1 / 0", 1, Value::Null),
+            Data("Start date + 100000000000", 1, Value::Null),
+            Data("Start date - 100000000000", 1, Value::Null),
+            Data("Start date + 10 * 9999999999", 1, Value::Null),
+            Data(
+                "lower(Category) + '-' + Complexity",
+                1,
+                Value::Str("dairy-Simple".to_string()),
+            ),
+        ];
+
+        for Data(input, project, expected) in cases {
+            let e = Expr::parse(input).unwrap();
+            assert_eq!(e.eval(&get_project(project), now), expected, "{}", input);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::io::{self, BufWriter, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process;
+
+const BOM: &str = "\u{feff}";
+
+// open opens input file and skips BOM,
+// returns file and whether BOM was present
+pub fn open(path: &Path) -> io::Result<(File, bool)> {
+    let mut file = File::open(path)?;
+    // check for BOM
+    let mut buf = [0; 3];
+    let n = file.read(&mut buf)?;
+
+    let has_bom = n == buf.len() && buf == BOM.as_bytes();
+    if !has_bom {
+        file.seek(SeekFrom::Start(0))?;
+    }
+
+    Ok((file, has_bom))
+}
+
+// write_atomic writes into temp file in the same directory as path
+// and renames it to path on success, so path is never left half-written,
+// temp file is removed on error
+pub fn write_atomic<F>(path: &Path, bom: bool, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+{
+    write_temp(path, bom, write, |tmp| fs::rename(tmp, path))
+}
+
+// write_atomic_new is write_atomic which fails with AlreadyExists error
+// if path exists, existing file is never replaced
+pub fn write_atomic_new<F>(path: &Path, bom: bool, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+{
+    if path.exists() {
+        return Err(already_exists(path));
+    }
+    // hard link fails if path was created meanwhile
+    write_temp(path, bom, write, |tmp| {
+        fs::hard_link(tmp, path).map_err(|err| match err.kind() {
+            io::ErrorKind::AlreadyExists => already_exists(path),
+            _ => err,
+        })?;
+        fs::remove_file(tmp)
+    })
+}
+
+fn already_exists(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{} already exists", path.display()),
+    )
+}
+
+// write_temp writes into temp file and moves it to path with finish
+fn write_temp<F, M>(path: &Path, bom: bool, write: F, finish: M) -> io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+    M: FnOnce(&Path) -> io::Result<()>,
+{
+    let tmp = temp_path(path);
+    let result = File::create(&tmp).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        if bom {
+            writer.write_all(BOM.as_bytes())?;
+        }
+        write(&mut writer)?;
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        finish(&tmp)
+    });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = env::temp_dir().join(format!("sad_write_atomic_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.tsv");
+
+        write_atomic(&path, true, |w| w.write_all(b"first\n")).unwrap();
+        let (mut file, bom) = open(&path).unwrap();
+        let mut s = String::new();
+        file.read_to_string(&mut s).unwrap();
+        assert!(bom);
+        assert_eq!(s, "first\n");
+
+        // failed write keeps previous content and removes temp file
+        let err = write_atomic(&path, false, |w| {
+            w.write_all(b"second\n")?;
+            Err(io::Error::other("failed"))
+        });
+        assert!(err.is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"\xef\xbb\xbffirst\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_atomic_new() {
+        let dir = env::temp_dir().join(format!("sad_write_atomic_new_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.tsv");
+
+        write_atomic_new(&path, false, |w| w.write_all(b"first\n")).unwrap();
+        let err = write_atomic_new(&path, false, |w| w.write_all(b"second\n")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&path).unwrap(), b"first\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+use project::Project;
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+use storage::Storage;
+
+// quote returns s as json string literal
+pub fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// object returns json object from key, json value pairs
+pub fn object<K: AsRef<str>, V: AsRef<str>>(fields: &[(K, V)]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(k, v)| format!("{}:{}", quote(k.as_ref()), v.as_ref()))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+// array returns json array from json values
+pub fn array<V: AsRef<str>>(values: &[V]) -> String {
+    let values: Vec<&str> = values.iter().map(|v| v.as_ref()).collect();
+    format!("[{}]", values.join(","))
+}
+
+// project returns json object with project values of columns
+// as printed in output
+pub fn project(p: &Project, columns: &[&str]) -> String {
+    let fields: Vec<(&str, String)> = columns
+        .iter()
+        .map(|c| (*c, quote(&p.get(c).unwrap_or_default())))
+        .collect();
+    object(&fields)
+}
+
+// write_projects writes storage projects of columns as json array,
+// one project per line
+pub fn write_projects<W: Write>(
+    mut writer: W,
+    storage: &Storage,
+    columns: &[&str],
+) -> io::Result<()> {
+    writer.write_all(b"[")?;
+    for (i, p) in storage.iter().enumerate() {
+        writer.write_all(if i == 0 { b"\n" } else { b",\n" })?;
+        writer.write_all(project(p, columns).as_bytes())?;
+    }
+    writer.write_all(b"\n]\n")?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::*;
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            quote("Crème \"fraîche\"\t\\\u{1}"),
+            r#""Crème \"fraîche\"\t\\\u0001""#
+        );
+        assert_eq!(
+            object(&[("a", quote("1")), ("b", array(&["1", "2"]))]),
+            r#"{"a":"1","b":[1,2]}"#
+        );
+        assert_eq!(array::<&str>(&[]), "[]");
+    }
+
+    #[test]
+    fn test_write_projects() {
+        let mut storage = Storage::new(vec![]);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+
+        let mut buf: Vec<u8> = Vec::new();
+        write_projects(&mut buf, &storage, &["Project", "Currency"]).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "[\n{\"Project\":\"1\",\"Currency\":\"\"},\n{\"Project\":\"2\",\"Currency\":\"EUR\"}\n]\n"
+        );
+    }
+}
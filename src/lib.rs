@@ -1,17 +1,52 @@
+#[cfg(feature = "parquet")]
+extern crate arrow_array;
+#[cfg(feature = "parquet")]
+extern crate arrow_schema;
 extern crate chrono;
+#[cfg(feature = "parquet")]
+extern crate parquet;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+#[cfg(feature = "xlsx")]
+extern crate rust_xlsxwriter;
+extern crate toml;
+#[cfg(all(test, feature = "xlsx"))]
+extern crate zip;
 
 pub mod api;
+#[cfg(feature = "parquet")]
+pub mod columnar;
+pub mod diff;
+pub mod expr;
+pub mod file;
+pub mod json;
 pub mod parser;
+pub mod partition;
+pub mod pivot;
+pub mod query;
+pub mod report;
+pub mod search;
+pub mod series;
+pub mod server;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stats;
 pub mod storage;
+pub mod table;
+pub mod template;
+pub mod watch;
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
 
 pub mod project {
     use chrono::NaiveDateTime;
-    use std::collections::HashMap;
+    use std::cmp::Ordering;
+    use std::collections::{BTreeMap, HashMap};
     use std::fmt;
 
     pub const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S.%3f";
     pub const NULL_STR: &str = "NULL";
-    pub const COLUMN_NAMES: [&'static str; 8] = [
+    pub const COLUMN_NAMES: [&str; 8] = [
         "Project",
         "Description",
         "Start date",
@@ -22,7 +57,7 @@ pub mod project {
         "Complexity",
     ];
 
-    #[derive(Debug, Clone, PartialEq)]
+    #[derive(Debug, Clone)]
     pub struct Project {
         pub id: String,
         pub description: String,
@@ -32,13 +67,36 @@ pub mod project {
         pub savings_amount: Option<Money>,
         pub currency: Option<Currency>,
         pub complexity: Complexity,
+        // columns added to project after parsing, e.g. by join
+        pub extra: BTreeMap<String, Value>,
+        // text of values as read by column where it differs from
+        // input format, e.g. untrimmed or 1.5 for 1.500000
+        pub raw: BTreeMap<String, String>,
+    }
+
+    // projects are equal by values, however they were written
+    impl PartialEq for Project {
+        fn eq(&self, other: &Project) -> bool {
+            self.id == other.id
+                && self.description == other.description
+                && self.start_date == other.start_date
+                && self.category == other.category
+                && self.responsible == other.responsible
+                && self.savings_amount == other.savings_amount
+                && self.currency == other.currency
+                && self.complexity == other.complexity
+                && self.extra == other.extra
+        }
     }
 
     impl Project {
-        pub fn new(record: Vec<&str>) -> Result<Project, String> {
-            if record.len() != 8 {
+        // new parses record of values of project columns,
+        // values are trimmed and their text is kept
+        pub fn new(raw: Vec<&str>) -> Result<Project, String> {
+            if raw.len() != 8 {
                 return Err("wrong number of columns".to_string());
             }
+            let record: Vec<&str> = raw.iter().map(|v| v.trim()).collect();
             let start_date: NaiveDateTime;
 
             if let Ok(v) = NaiveDateTime::parse_from_str(record[2], DATE_FORMAT) {
@@ -47,7 +105,7 @@ pub mod project {
                 return Err(format!("start date parse error {}", record[2]));
             }
 
-            Ok(Project {
+            let mut p = Project {
                 id: String::from(record[0]),
                 description: String::from(record[1]),
                 start_date,
@@ -56,38 +114,277 @@ pub mod project {
                 savings_amount: Money::from_str(record[5])?,
                 currency: Currency::from_str(record[6])?,
                 complexity: Complexity::from_str(record[7])?,
-            })
+                extra: BTreeMap::new(),
+                raw: BTreeMap::new(),
+            };
+            for (c, v) in COLUMN_NAMES.iter().zip(raw) {
+                p.keep_raw(c, v);
+            }
+            Ok(p)
         }
 
-        pub fn as_dic(&self) -> HashMap<&str, String> {
-            let mut dic: HashMap<&str, String> = HashMap::with_capacity(8);
+        // get returns value of column as printed in output,
+        // None if column is unknown
+        pub fn get(&self, column: &str) -> Option<String> {
+            self.value(column, "")
+        }
 
-            let savings_amount = match &self.savings_amount {
-                Some(ref v) => v.to_string(),
-                _ => String::from(""),
+        // get_source returns value of column in input format, as text it
+        // was read from while that still holds the value,
+        // None if column is unknown
+        pub fn get_source(&self, column: &str) -> Option<String> {
+            let v = self.value(column, NULL_STR)?;
+            match self.raw.get(column) {
+                Some(raw) if self.holds(column, raw) => Some(raw.clone()),
+                _ => Some(v),
+            }
+        }
+
+        // keep_raw keeps text value of column was read from,
+        // if it differs from input format of the value
+        pub fn keep_raw(&mut self, column: &str, raw: &str) {
+            if self.value(column, NULL_STR).is_some_and(|v| v != raw) {
+                self.raw.insert(column.to_string(), raw.to_string());
+            } else {
+                self.raw.remove(column);
+            }
+        }
+
+        // holds reports whether raw text parses to value of column
+        fn holds(&self, column: &str, raw: &str) -> bool {
+            match self.value_of(column) {
+                Some(Value::Null) => raw.trim() == NULL_STR,
+                Some(v) => v
+                    .get_type()
+                    .is_some_and(|t| Value::parse(t, raw.trim()) == Ok(v)),
+                None => false,
+            }
+        }
+
+        fn value(&self, column: &str, null: &str) -> Option<String> {
+            let v = match column {
+                "Project" => self.id.clone(),
+                "Description" => self.description.clone(),
+                "Start date" => self.start_date.format(DATE_FORMAT).to_string(),
+                "Category" => self.category.clone(),
+                "Responsible" => self.responsible.clone(),
+                "Savings amount" => match self.savings_amount {
+                    Some(ref v) => v.to_string(),
+                    None => String::from(null),
+                },
+                "Currency" => match self.currency {
+                    Some(ref v) => v.to_string(),
+                    None => String::from(null),
+                },
+                "Complexity" => self.complexity.to_string(),
+                _ => {
+                    return self.extra.get(column).map(|v| match v {
+                        Value::Null => String::from(null),
+                        v => v.to_string(),
+                    })
+                }
             };
+            Some(v)
+        }
 
-            let currency = match &self.currency {
-                Some(ref v) => v.to_string(),
-                _ => String::from(""),
+        // value_of returns typed value of column, None if column is unknown
+        pub fn value_of(&self, column: &str) -> Option<Value> {
+            let v = match column {
+                "Project" => Value::Str(self.id.clone()),
+                "Description" => Value::Str(self.description.clone()),
+                "Start date" => Value::Date(self.start_date),
+                "Category" => Value::Str(self.category.clone()),
+                "Responsible" => Value::Str(self.responsible.clone()),
+                "Savings amount" => match self.savings_amount {
+                    Some(ref v) => Value::Number(v.0),
+                    None => Value::Null,
+                },
+                "Currency" => match self.currency {
+                    Some(ref v) => Value::Str(v.to_string()),
+                    None => Value::Null,
+                },
+                "Complexity" => Value::Str(self.complexity.to_string()),
+                _ => return self.extra.get(column).cloned(),
             };
+            Some(v)
+        }
+
+        // set parses value in input format and assigns it to column
+        pub fn set(&mut self, column: &str, value: &str) -> Result<(), String> {
+            match column {
+                "Project" => self.id = String::from(value),
+                "Description" => self.description = String::from(value),
+                "Start date" => {
+                    self.start_date = match NaiveDateTime::parse_from_str(value, DATE_FORMAT) {
+                        Ok(v) => v,
+                        Err(_) => return Err(format!("start date parse error {}", value)),
+                    }
+                }
+                "Category" => self.category = String::from(value),
+                "Responsible" => self.responsible = String::from(value),
+                "Savings amount" => self.savings_amount = Money::from_str(value)?,
+                "Currency" => self.currency = Currency::from_str(value)?,
+                "Complexity" => self.complexity = Complexity::from_str(value)?,
+                // type of missing value is not known here,
+                // storage sets extra columns by their column types
+                _ => match self.extra.get(column) {
+                    Some(v) => {
+                        let t = v.get_type().unwrap_or(Type::Str);
+                        return self.set_extra(column, t, value);
+                    }
+                    None => return Err(format!("unknown column {}", column)),
+                },
+            }
+            self.keep_raw(column, value);
+            Ok(())
+        }
+
+        // set_extra parses value in input format as type t
+        // and assigns it to extra column
+        pub fn set_extra(&mut self, column: &str, t: Type, value: &str) -> Result<(), String> {
+            self.extra
+                .insert(column.to_string(), Value::parse(t, value)?);
+            self.keep_raw(column, value);
+            Ok(())
+        }
+
+        // compare orders projects by typed value of column,
+        // missing values go first, complexity is ordered by level
+        pub fn compare(&self, other: &Project, column: &str) -> Ordering {
+            match column {
+                "Complexity" => self.complexity.cmp(&other.complexity),
+                _ => self.value_of(column).cmp(&other.value_of(column)),
+            }
+        }
 
-            dic.insert(COLUMN_NAMES[0], self.id.clone());
-            dic.insert(COLUMN_NAMES[1], self.description.clone());
-            dic.insert(
-                COLUMN_NAMES[2],
-                self.start_date.format(DATE_FORMAT).to_string(),
-            );
-            dic.insert(COLUMN_NAMES[3], self.category.clone());
-            dic.insert(COLUMN_NAMES[4], self.responsible.clone());
-            dic.insert(COLUMN_NAMES[5], savings_amount);
-            dic.insert(COLUMN_NAMES[6], currency);
-            dic.insert(COLUMN_NAMES[7], self.complexity.to_string());
+        pub fn as_dic(&self) -> HashMap<&str, String> {
+            let mut dic: HashMap<&str, String> = HashMap::with_capacity(8);
+            for c in COLUMN_NAMES.iter() {
+                dic.insert(c, self.get(c).unwrap());
+            }
+            for (c, v) in self.extra.iter() {
+                dic.insert(c, v.to_string());
+            }
 
             dic
         }
     }
 
+    // column_type returns type of column of input file
+    pub fn column_type(column: &str) -> Option<Type> {
+        match column {
+            "Start date" => Some(Type::Date),
+            "Savings amount" => Some(Type::Number),
+            c if COLUMN_NAMES.contains(&c) => Some(Type::Str),
+            _ => None,
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Type {
+        Str,
+        Number,
+        Date,
+    }
+
+    impl fmt::Display for Type {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Type::Str => write!(f, "string"),
+                Type::Number => write!(f, "number"),
+                Type::Date => write!(f, "date"),
+            }
+        }
+    }
+
+    // Value is a typed column value, Null is a missing value
+    #[derive(Debug, Clone)]
+    pub enum Value {
+        Null,
+        Str(String),
+        Number(f64),
+        Date(NaiveDateTime),
+    }
+
+    impl Value {
+        // parse parses value of type in input format
+        pub fn parse(t: Type, s: &str) -> Result<Value, String> {
+            if s == NULL_STR {
+                return Ok(Value::Null);
+            }
+            match t {
+                Type::Str => Ok(Value::Str(String::from(s))),
+                Type::Number => match s.parse() {
+                    Ok(v) => Ok(Value::Number(v)),
+                    Err(e) => Err(format!("number parse error {}", e)),
+                },
+                Type::Date => match NaiveDateTime::parse_from_str(s, DATE_FORMAT) {
+                    Ok(v) => Ok(Value::Date(v)),
+                    Err(_) => Err(format!("date parse error {}", s)),
+                },
+            }
+        }
+
+        pub fn get_type(&self) -> Option<Type> {
+            match self {
+                Value::Null => None,
+                Value::Str(_) => Some(Type::Str),
+                Value::Number(_) => Some(Type::Number),
+                Value::Date(_) => Some(Type::Date),
+            }
+        }
+
+        fn rank(&self) -> u8 {
+            match self {
+                Value::Null => 0,
+                Value::Number(_) => 1,
+                Value::Date(_) => 2,
+                Value::Str(_) => 3,
+            }
+        }
+    }
+
+    impl PartialEq for Value {
+        fn eq(&self, other: &Value) -> bool {
+            self.cmp(other) == Ordering::Equal
+        }
+    }
+
+    impl Eq for Value {}
+
+    impl PartialOrd for Value {
+        fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Value {
+        fn cmp(&self, other: &Value) -> Ordering {
+            match (self, other) {
+                (Value::Number(a), Value::Number(b)) => a.total_cmp(b),
+                (Value::Date(a), Value::Date(b)) => a.cmp(b),
+                (Value::Str(a), Value::Str(b)) => a.cmp(b),
+                (a, b) => a.rank().cmp(&b.rank()),
+            }
+        }
+    }
+
+    // Value is printed as in output, Null as empty string,
+    // whole numbers without fraction
+    impl fmt::Display for Value {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Value::Null => Ok(()),
+                Value::Str(s) => write!(f, "{}", s),
+                Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => {
+                    write!(f, "{}", *n as i64)
+                }
+                Value::Number(n) => write!(f, "{}", n),
+                Value::Date(d) => write!(f, "{}", d.format(DATE_FORMAT)),
+            }
+        }
+    }
+
     #[derive(Debug, Clone, PartialEq)]
     pub enum Currency {
         EUR,
@@ -116,12 +413,15 @@ pub mod project {
         pub fn new(f: f64) -> Money {
             Money(f)
         }
+        pub fn value(&self) -> f64 {
+            self.0
+        }
         fn from_str(s: &str) -> Result<Option<Money>, String> {
             match s {
                 NULL_STR => Ok(None),
                 _ => match s.parse() {
                     Ok(v) => Ok(Some(Money(v))),
-                    Err(e) => return Err(format!("money parse error {}", e)),
+                    Err(e) => Err(format!("money parse error {}", e)),
                 },
             }
         }
@@ -133,7 +433,7 @@ pub mod project {
         }
     }
 
-    #[derive(Debug, Clone, PartialEq)]
+    #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
     pub enum Complexity {
         Simple,
         Moderate,
@@ -177,6 +477,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn project_get_and_set() {
+        let mut p = get_project(1);
+        p.set("Savings amount", "10.5").unwrap();
+        p.set("Currency", "EUR").unwrap();
+        p.set("Category", "Meat").unwrap();
+        assert_eq!(p.get("Savings amount").unwrap(), "10.500000");
+        assert_eq!(p.get("Currency").unwrap(), "EUR");
+        assert_eq!(p.get("Category").unwrap(), "Meat");
+        assert_eq!(p.get("Unknown"), None);
+
+        p.extra
+            .insert("Department".to_string(), Value::Str("R&D".to_string()));
+        p.set("Department", "Sales").unwrap();
+        assert_eq!(p.get("Department").unwrap(), "Sales");
+        assert_eq!(p.get_source("Department").unwrap(), "Sales");
+
+        p.extra.insert("Year".to_string(), Value::Number(2014.0));
+        p.set("Year", "2015").unwrap();
+        assert_eq!(p.value_of("Year").unwrap(), Value::Number(2015.0));
+        assert!(p.set("Year", "next").is_err());
+        p.set("Year", "NULL").unwrap();
+        assert_eq!(p.get("Year").unwrap(), "");
+        assert_eq!(p.get_source("Year").unwrap(), "NULL");
+
+        p.set("Savings amount", "NULL").unwrap();
+        assert_eq!(p.get("Savings amount").unwrap(), "");
+        assert_eq!(p.get_source("Savings amount").unwrap(), "NULL");
+        assert!(p.set("Complexity", "Easy").is_err());
+        assert!(p.set("Unknown", "1").is_err());
+        assert_eq!(p.get("Complexity").unwrap(), "Simple");
+    }
+
     #[test]
     fn project_as_dic() {
         struct Data(Project, HashMap<&'static str, String>);
@@ -194,13 +527,14 @@ mod tests {
 pub mod test_util {
     use chrono::NaiveDate;
     use project::*;
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
+    use storage::Storage;
 
     pub fn get_project_as_dic(id: usize) -> HashMap<&'static str, String> {
         let mut p1: HashMap<&str, String> = HashMap::new();
         let mut p2: HashMap<&str, String> = HashMap::new();
 
-        let vals1 = vec![
+        let vals1 = [
             "1",
             "Harmonize Lactobacillus acidophilus sourcing",
             "2014-01-01 00:00:00.000",
@@ -210,7 +544,7 @@ pub mod test_util {
             "",
             "Simple",
         ];
-        let vals2 = vec![
+        let vals2 = [
             "2",
             "Substitute Crème fraîche with evaporated milk in ice-cream products",
             "2013-01-01 00:00:00.000",
@@ -228,12 +562,12 @@ pub mod test_util {
             p2.insert(COLUMN_NAMES[i], v.to_string());
         }
 
-        let records = vec![p1, p2];
+        let records = [p1, p2];
 
         records[id - 1].clone()
     }
     pub fn get_record(id: usize) -> Vec<&'static str> {
-        let records = vec![
+        let records = [
             vec![
                 "1",
                 "Harmonize Lactobacillus acidophilus sourcing",
@@ -264,28 +598,45 @@ pub mod test_util {
             Project {
                 id: String::from("1"),
                 description: String::from("Harmonize Lactobacillus acidophilus sourcing"),
-                start_date: NaiveDate::from_ymd(2014, 1, 1).and_hms_milli(0, 0, 0, 000),
+                start_date: NaiveDate::from_ymd_opt(2014, 1, 1)
+                    .unwrap()
+                    .and_hms_milli_opt(0, 0, 0, 000)
+                    .unwrap(),
                 category: String::from("Dairy"),
                 responsible: String::from("Daisy Milks"),
                 savings_amount: None,
                 currency: None,
                 complexity: Complexity::Simple,
+                extra: BTreeMap::new(),
+                raw: BTreeMap::new(),
             },
             Project {
                 id: String::from("2"),
                 description: String::from(
                     "Substitute Crème fraîche with evaporated milk in ice-cream products",
                 ),
-                start_date: NaiveDate::from_ymd(2013, 1, 1).and_hms_milli(0, 0, 0, 000),
+                start_date: NaiveDate::from_ymd_opt(2013, 1, 1)
+                    .unwrap()
+                    .and_hms_milli_opt(0, 0, 0, 000)
+                    .unwrap(),
                 category: String::from("Dairy"),
                 responsible: String::from("Daisy Milks"),
                 savings_amount: Some(Money::new(141415.942696)),
                 currency: Some(Currency::EUR),
                 complexity: Complexity::Moderate,
+                extra: BTreeMap::new(),
+                raw: BTreeMap::new(),
             },
         ];
 
         projects[id - 1].clone()
     }
-
+    // get_storage returns storage of records in order
+    pub fn get_storage(records: Vec<Vec<&str>>) -> Storage {
+        let mut storage = Storage::new(vec![]);
+        for r in records {
+            storage.insert(r).unwrap();
+        }
+        storage
+    }
 }
@@ -1,53 +1,248 @@
-use std::collections::HashMap;
-use std::io::{BufRead, Write};
-use storage::Storage;
+use project::{COLUMN_NAMES, NULL_STR};
+use std::io::{self, BufRead, Write};
+use storage::{Layout, Lookup, Storage};
 
 const SEP: &str = "\t";
 const COMMENT_SYMBOL: &str = "#";
 
-// parse_tsv parses line by line in tsv format and puts into storage
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    // keep blank lines in storage with their positions,
+    // so they can be written back with write_source_tsv
+    pub keep_blank_lines: bool,
+}
+
+// parse_tsv parses line by line in tsv format and puts into storage,
+// comment lines are kept in storage with their positions
 // panics on errors
 pub fn parse_tsv<R: BufRead>(reader: R, storage: &mut Storage) {
+    parse_tsv_with_options(reader, storage, ParseOptions::default())
+}
+
+// parse_tsv_with_options is parse_tsv with non default options
+// panics on errors
+pub fn parse_tsv_with_options<R: BufRead>(reader: R, storage: &mut Storage, opts: ParseOptions) {
+    if let Err(errors) = try_parse_tsv(reader, storage, opts) {
+        panic!("{}", errors[0]);
+    }
+}
+
+// try_parse_tsv is parse_tsv which does not stop on invalid lines,
+// valid projects are put into storage and errors of all invalid lines returned.
+// Line endings, header and values are kept as read for write_source_tsv
+pub fn try_parse_tsv<R: BufRead>(
+    mut reader: R,
+    storage: &mut Storage,
+    opts: ParseOptions,
+) -> Result<(), Vec<String>> {
+    let mut errors = vec![];
     let mut is_header = true;
-    for (i, line) in reader.lines().enumerate() {
-        let s = line.unwrap();
+    let mut layout = Layout::default();
+    let mut line = String::new();
+    for i in 0.. {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(err) => {
+                errors.push(format!("on line: {}, read error: {}", i + 1, err));
+                break;
+            }
+        }
+        let s = match line.strip_suffix('\n') {
+            Some(s) => {
+                if i == 0 {
+                    layout.crlf = s.ends_with('\r');
+                }
+                s.strip_suffix('\r').unwrap_or(s)
+            }
+            None => {
+                layout.final_newline = false;
+                line.as_str()
+            }
+        };
+        let row = if is_header {
+            None
+        } else {
+            Some(storage.size())
+        };
         // skip empty and comment lines
-        if s.is_empty() || s.trim().starts_with(COMMENT_SYMBOL) {
+        if s.is_empty() {
+            if opts.keep_blank_lines {
+                storage.annotate(s, row);
+            }
+            continue;
+        }
+        if s.trim().starts_with(COMMENT_SYMBOL) {
+            storage.annotate(s, row);
             continue;
         }
-
-        let record: Vec<&str> = s.split(SEP).map(|s| s.trim()).collect();
 
         if !is_header {
-            storage.insert(record).unwrap_or_else(|err| {
-                panic!("on line: {}, parse error: {}", i + 1, err);
-            });
+            // values are trimmed by parsing, raw text is kept
+            let record: Vec<&str> = s.split(SEP).collect();
+            if let Err(err) = storage.insert(record) {
+                errors.push(format!("on line: {}, parse error: {}", i + 1, err));
+            }
         } else {
+            let record: Vec<&str> = s.split(SEP).map(|s| s.trim()).collect();
+            // projects can not be parsed without required columns
+            if let Some(c) = COLUMN_NAMES.iter().find(|c| !record.contains(c)) {
+                errors.push(format!("on line: {}, header misses column {}", i + 1, c));
+                break;
+            }
             storage.set_columns(record);
+            layout.header = Some(s.to_string());
             is_header = false;
         }
     }
+    storage.set_layout(layout);
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+// parse_lookup_tsv parses tsv format lookup table with header,
+// key is the name of key column, first column by default
+pub fn parse_lookup_tsv<R: BufRead>(reader: R, key: Option<&str>) -> Result<Lookup, String> {
+    let mut lookup: Option<(usize, Lookup)> = None;
+    for (i, line) in reader.lines().enumerate() {
+        let s = line.map_err(|e| e.to_string())?;
+        // skip empty and comment lines
+        if s.is_empty() || s.trim().starts_with(COMMENT_SYMBOL) {
+            continue;
+        }
+        let mut record: Vec<String> = s.split(SEP).map(|s| s.trim().to_string()).collect();
+
+        match lookup {
+            Some((k, ref mut lookup)) => {
+                if record.len() != lookup.columns.len() + 1 {
+                    return Err(format!("on line: {}, wrong number of columns", i + 1));
+                }
+                let key = record.remove(k);
+                lookup
+                    .insert(&key, record)
+                    .map_err(|err| format!("on line: {}, {}", i + 1, err))?;
+            }
+            None => {
+                let k = match key {
+                    Some(key) => match record.iter().position(|c| c == key) {
+                        Some(k) => k,
+                        None => return Err(format!("lookup key column {} not found", key)),
+                    },
+                    None => 0,
+                };
+                let key = record.remove(k);
+                lookup = Some((k, Lookup::new(&key, record)));
+            }
+        }
+    }
+
+    match lookup {
+        Some((_, lookup)) => Ok(lookup),
+        None => Err("lookup header not found".to_string()),
+    }
 }
 
 // write_tsv writes tsv format storage data into writer
-// panics on errors
-pub fn write_tsv<W: Write>(mut writer: W, storage: &Storage) {
-    let columns = storage.get_columns();
-    writer.write(columns.join(SEP).as_bytes()).unwrap();
-    writer.write(b"\n").unwrap();
+pub fn write_tsv<W: Write>(writer: W, storage: &Storage) -> io::Result<()> {
+    write_tsv_columns(writer, storage, &storage.get_columns())
+}
+
+// write_tsv_columns writes tsv format storage data of columns into writer,
+// unknown columns have empty values
+pub fn write_tsv_columns<W: Write>(
+    mut writer: W,
+    storage: &Storage,
+    columns: &[&str],
+) -> io::Result<()> {
+    writer.write_all(columns.join(SEP).as_bytes())?;
+    writer.write_all(b"\n")?;
 
-    let mut vals: Vec<String> = Vec::with_capacity(8);
-    let mut dic: HashMap<&str, String>;
+    let mut vals: Vec<String> = Vec::with_capacity(columns.len());
     for p in storage.iter() {
-        dic = p.as_dic();
         columns.iter().for_each(|c| {
-            vals.push(dic.get(c as &str).unwrap().to_owned());
+            vals.push(p.get(c).unwrap_or_default());
         });
 
-        writer.write(vals.join(SEP).as_bytes()).unwrap();
-        writer.write(b"\n").unwrap();
+        writer.write_all(vals.join(SEP).as_bytes())?;
+        writer.write_all(b"\n")?;
         vals.clear();
     }
+
+    writer.flush()
+}
+
+// write_source_tsv writes storage data in input format,
+// so output can be parsed again. Comment and blank lines kept in storage
+// are written at their original positions, if projects were reordered
+// comment lines are written before header and blank lines are dropped.
+// Line endings, header and values are written as they were read
+pub fn write_source_tsv<W: Write>(writer: W, storage: &Storage) -> io::Result<()> {
+    let layout = storage.layout();
+    let mut lines = Lines {
+        writer,
+        ending: if layout.crlf { "\r\n" } else { "\n" },
+        started: false,
+    };
+    let reordered = storage.is_reordered();
+    let mut annotations = storage.annotations().iter().peekable();
+    let mut write_until = |lines: &mut Lines<W>, row: Option<usize>| -> io::Result<()> {
+        while let Some(a) = annotations.next_if(|a| reordered || a.row <= row) {
+            if reordered && a.line.is_empty() {
+                continue;
+            }
+            lines.write(&a.line)?;
+        }
+        Ok(())
+    };
+
+    write_until(&mut lines, None)?;
+    let columns = storage.get_columns();
+    match &layout.header {
+        Some(h) if h.split(SEP).map(|c| c.trim()).eq(columns.iter().copied()) => lines.write(h)?,
+        _ => lines.write(&columns.join(SEP))?,
+    }
+
+    for (i, p) in storage.iter().enumerate() {
+        write_until(&mut lines, Some(i))?;
+        let vals: Vec<String> = columns
+            .iter()
+            .map(|c| p.get_source(c).unwrap_or_else(|| NULL_STR.to_string()))
+            .collect();
+        lines.write(&vals.join(SEP))?;
+    }
+    write_until(&mut lines, Some(storage.size()))?;
+
+    lines.finish(layout.final_newline)
+}
+
+// Lines writes lines separated by line ending
+struct Lines<W: Write> {
+    writer: W,
+    ending: &'static str,
+    started: bool,
+}
+
+impl<W: Write> Lines<W> {
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        if self.started {
+            self.writer.write_all(self.ending.as_bytes())?;
+        }
+        self.started = true;
+        self.writer.write_all(line.as_bytes())
+    }
+
+    // finish ends last line if final_newline is set
+    fn finish(mut self, final_newline: bool) -> io::Result<()> {
+        if final_newline && self.started {
+            self.writer.write_all(self.ending.as_bytes())?;
+        }
+        self.writer.flush()
+    }
 }
 
 #[cfg(test)]
@@ -55,6 +250,8 @@ mod tests {
     use std::str;
 
     use super::*;
+    use project::Type;
+    use test_util::*;
 
     #[test]
     fn test_parse_tsv() {
@@ -90,6 +287,38 @@ Moderate	2	Substitute Crème fraîche with evaporated milk in ice-cream products
         }
     }
 
+    #[test]
+    fn test_try_parse_tsv() {
+        let input = "Project	Description	Start date	Category	Responsible	Savings amount	Currency	Complexity
+1	Harmonize Lactobacillus acidophilus sourcing	2014-01-01 00:00:00.000	Dairy	Daisy Milks	NULL	NULL	Simple
+2	Substitute Crème fraîche	2013-01-01	Dairy	Daisy Milks	141415.942696	EUR	Moderate
+# comment line
+3	Short line
+4	Harmonize Lactobacillus acidophilus sourcing	2014-01-01 00:00:00.000	Dairy	Daisy Milks	NULL	USD	Simple";
+        let mut storage = Storage::new(vec![]);
+        let errors =
+            try_parse_tsv(input.as_bytes(), &mut storage, ParseOptions::default()).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "on line: 3, parse error: start date parse error 2013-01-01",
+                "on line: 5, parse error: wrong number of columns",
+                "on line: 6, parse error: currency parse error USD",
+            ]
+        );
+        // valid projects are kept
+        assert_eq!(storage.size(), 1);
+
+        let input = "Project	Description	Category\n1	Harmonize	Dairy";
+        let errors = try_parse_tsv(
+            input.as_bytes(),
+            &mut Storage::new(vec![]),
+            ParseOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(errors, vec!["on line: 1, header misses column Start date"]);
+    }
+
     #[test]
     fn test_write_tsv() {
         // column, projects, output
@@ -151,7 +380,7 @@ Simple	1	Harmonize Lactobacillus acidophilus sourcing	2014-01-01 00:00:00.000	Da
             }
 
             let mut buf: Vec<u8> = Vec::new();
-            write_tsv(&mut buf, &mut storage);
+            write_tsv(&mut buf, &storage).unwrap();
 
             assert_eq!(str::from_utf8(buf.as_slice()).unwrap(), expected);
 
@@ -159,4 +388,196 @@ Simple	1	Harmonize Lactobacillus acidophilus sourcing	2014-01-01 00:00:00.000	Da
         }
     }
 
+    #[test]
+    fn test_write_source_tsv() {
+        let input = "# first comment
+Project	Description	Start date	Category	Responsible	Savings amount	Currency	Complexity
+# second comment
+1	Harmonize Lactobacillus acidophilus sourcing	2014-01-01 00:00:00.000	Dairy	Daisy Milks	NULL	NULL	Simple
+
+2	Substitute Crème fraîche with evaporated milk in ice-cream products	2013-01-01 00:00:00.000	Dairy	Daisy Milks	141415.942696	EUR	Moderate
+# last comment
+";
+        let sorted = "# first comment
+# second comment
+# last comment
+Project	Description	Start date	Category	Responsible	Savings amount	Currency	Complexity
+2	Substitute Crème fraîche with evaporated milk in ice-cream products	2013-01-01 00:00:00.000	Dairy	Daisy Milks	141415.942696	EUR	Moderate
+1	Harmonize Lactobacillus acidophilus sourcing	2014-01-01 00:00:00.000	Dairy	Daisy Milks	NULL	NULL	Simple
+";
+        let opts = ParseOptions {
+            keep_blank_lines: true,
+        };
+        let mut storage: Storage = Storage::new(vec![Box::new(|_p| true)]);
+        parse_tsv_with_options(Box::new(input.as_bytes()), &mut storage, opts);
+
+        let mut buf: Vec<u8> = Vec::new();
+        write_source_tsv(&mut buf, &storage).unwrap();
+        assert_eq!(str::from_utf8(buf.as_slice()).unwrap(), input);
+
+        storage.sort_by_start_date();
+        let mut buf: Vec<u8> = Vec::new();
+        write_source_tsv(&mut buf, &storage).unwrap();
+        assert_eq!(str::from_utf8(buf.as_slice()).unwrap(), sorted);
+
+        // blank lines are dropped by default
+        storage.clear();
+        storage.set_columns(COLUMN_NAMES.to_vec());
+        parse_tsv(Box::new(input.as_bytes()), &mut storage);
+        let mut buf: Vec<u8> = Vec::new();
+        write_source_tsv(&mut buf, &storage).unwrap();
+        assert_eq!(
+            str::from_utf8(buf.as_slice()).unwrap(),
+            input.replace("\n\n", "\n")
+        );
+    }
+
+    #[test]
+    fn test_write_source_tsv_amounts() {
+        let input = "Project	Description	Start date	Category	Responsible	Savings amount	Currency	Complexity
+1	Harmonize Lactobacillus acidophilus sourcing	2014-01-01 00:00:00.000	Dairy	Daisy Milks	1.5	EUR	Simple
+2	Substitute Crème fraîche with evaporated milk in ice-cream products	2013-01-01 00:00:00.000	Dairy	Daisy Milks	0.123456789	EUR	Moderate
+3	Black and white wall paper	2013-01-01 00:00:00.000	Office	Clark Kent	1e3	EUR	Simple
+";
+        let mut storage: Storage = Storage::new(vec![Box::new(|_p| true)]);
+        parse_tsv(Box::new(input.as_bytes()), &mut storage);
+
+        // amounts are written as read, updated ones in output format
+        storage
+            .update(|p| p.id == "3", "Savings amount", "2.25")
+            .unwrap();
+        let mut buf: Vec<u8> = Vec::new();
+        write_source_tsv(&mut buf, &storage).unwrap();
+        assert_eq!(
+            str::from_utf8(buf.as_slice()).unwrap(),
+            input.replace("1e3", "2.25")
+        );
+        storage.update(|p| p.id == "3", "Currency", "NULL").unwrap();
+        let mut buf: Vec<u8> = Vec::new();
+        write_source_tsv(&mut buf, &storage).unwrap();
+        assert_eq!(
+            str::from_utf8(buf.as_slice()).unwrap(),
+            input.replace("1e3\tEUR", "2.25\tNULL")
+        );
+    }
+
+    #[test]
+    fn test_write_source_tsv_layout() {
+        // crlf endings, padded header and values, no newline at the end
+        let input = "# projects\r\n\
+Project\tDescription \tStart date\tCategory\tResponsible\tSavings amount\tCurrency\tComplexity\r\n\
+1\t Harmonize Lactobacillus acidophilus sourcing \t2014-01-01 00:00:00.000\tDairy\tDaisy Milks\tNULL\tNULL\tSimple \r\n\
+\r\n\
+2\tSubstitute Crème fraîche with evaporated milk in ice-cream products\t2013-01-01 00:00:00.000\t Dairy\tDaisy Milks\t 1.5\tEUR\tModerate";
+        let opts = ParseOptions {
+            keep_blank_lines: true,
+        };
+        let mut storage: Storage = Storage::new(vec![]);
+        try_parse_tsv(input.as_bytes(), &mut storage, opts).unwrap();
+        let p = storage.iter().next().unwrap();
+        assert_eq!(
+            p.description,
+            "Harmonize Lactobacillus acidophilus sourcing"
+        );
+        assert_eq!(p.complexity.to_string(), "Simple");
+
+        let mut buf: Vec<u8> = Vec::new();
+        write_source_tsv(&mut buf, &storage).unwrap();
+        assert_eq!(str::from_utf8(buf.as_slice()).unwrap(), input);
+
+        // only changed value is written in input format
+        storage.update(|p| p.id == "2", "Category", "Meat").unwrap();
+        let mut buf: Vec<u8> = Vec::new();
+        write_source_tsv(&mut buf, &storage).unwrap();
+        assert_eq!(
+            str::from_utf8(buf.as_slice()).unwrap(),
+            input.replace("\t Dairy\t", "\tMeat\t")
+        );
+
+        // lf endings with final newline by default
+        let mut storage: Storage = Storage::new(vec![]);
+        try_parse_tsv(&b""[..], &mut storage, opts).unwrap();
+        storage.add(get_project(1));
+        let mut buf: Vec<u8> = Vec::new();
+        write_source_tsv(&mut buf, &storage).unwrap();
+        let output = String::from_utf8(buf).unwrap();
+        assert!(output.ends_with("\tSimple\n"));
+        assert!(!output.contains('\r'));
+    }
+
+    #[test]
+    fn test_write_source_tsv_extra_columns() {
+        let input = "Project	Description	Start date	Category	Responsible	Savings amount	Currency	Complexity	Notes
+1	Harmonize Lactobacillus acidophilus sourcing	2014-01-01 00:00:00.000	Dairy	Daisy Milks	NULL	NULL	Simple	call Bob
+2	Substitute Crème fraîche with evaporated milk in ice-cream products	2013-01-01 00:00:00.000	Dairy	Daisy Milks	141415.942696	EUR	Moderate	NULL
+";
+        let mut storage: Storage = Storage::new(vec![Box::new(|_p| true)]);
+        try_parse_tsv(input.as_bytes(), &mut storage, ParseOptions::default()).unwrap();
+        assert_eq!(storage.column_type("Notes"), Some(Type::Str));
+        assert_eq!(
+            storage.iter().next().unwrap().get("Notes").unwrap(),
+            "call Bob"
+        );
+
+        storage
+            .update(|p| p.id == "2", "Notes", "ask Clark")
+            .unwrap();
+        let mut buf: Vec<u8> = Vec::new();
+        write_source_tsv(&mut buf, &storage).unwrap();
+        assert_eq!(
+            str::from_utf8(buf.as_slice()).unwrap(),
+            input.replace("EUR\tModerate\tNULL", "EUR\tModerate\task Clark")
+        );
+
+        // projects without value of column are written with NULL
+        storage.add(get_project(1));
+        let mut buf: Vec<u8> = Vec::new();
+        write_source_tsv(&mut buf, &storage).unwrap();
+        assert!(str::from_utf8(buf.as_slice())
+            .unwrap()
+            .ends_with("\tSimple\tNULL\n"));
+
+        let mut storage: Storage = Storage::new(vec![]);
+        let errors = try_parse_tsv(
+            input.replace("\tcall Bob", "").as_bytes(),
+            &mut storage,
+            ParseOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(errors, ["on line: 2, parse error: wrong number of columns"]);
+    }
+
+    #[test]
+    fn test_parse_lookup_tsv() {
+        let input = "# departments
+Department	Responsible	Cost center
+Dairy	Daisy Milks	100
+Office	Clark Kent	200
+";
+        let lookup = parse_lookup_tsv(input.as_bytes(), Some("Responsible")).unwrap();
+        assert_eq!(lookup.key, "Responsible");
+        assert_eq!(lookup.columns, vec!["Department", "Cost center"]);
+        assert_eq!(lookup.size(), 2);
+        assert_eq!(lookup.get("Clark Kent").unwrap(), &vec!["Office", "200"]);
+
+        let lookup = parse_lookup_tsv(input.as_bytes(), None).unwrap();
+        assert_eq!(lookup.key, "Department");
+
+        assert!(parse_lookup_tsv(input.as_bytes(), Some("Unknown")).is_err());
+        assert!(parse_lookup_tsv("Key	Value\n1	a\n1	b\n".as_bytes(), None).is_err());
+        assert!(parse_lookup_tsv("Key	Value\n1\n".as_bytes(), None).is_err());
+    }
+
+    #[test]
+    fn test_write_tsv_columns() {
+        let mut storage: Storage = Storage::new(vec![Box::new(|_p| true)]);
+        storage.insert(get_record(2)).unwrap();
+
+        let mut buf: Vec<u8> = Vec::new();
+        write_tsv_columns(&mut buf, &storage, &["Savings amount", "Project"]).unwrap();
+        assert_eq!(
+            str::from_utf8(buf.as_slice()).unwrap(),
+            "Savings amount\tProject\n141415.942696\t2\n"
+        );
+    }
 }
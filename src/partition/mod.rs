@@ -0,0 +1,187 @@
+use project::Project;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use storage::Storage;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Column(String),
+}
+
+// Template is a path with {Column} placeholders replaced by column values
+// of projects, e.g. out/{Category}.tsv
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(s: &str) -> Result<Template, String> {
+        let mut parts = vec![];
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => return Err(format!("unclosed {{ in template {}", s)),
+            };
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let column = rest[start + 1..end].trim();
+            if column.is_empty() {
+                return Err(format!("empty column name in template {}", s));
+            }
+            parts.push(Part::Column(column.to_string()));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+
+        if !parts.iter().any(|p| matches!(p, Part::Column(_))) {
+            return Err(format!("template {} has no {{column}} placeholder", s));
+        }
+        Ok(Template { parts })
+    }
+
+    // columns returns columns of placeholders
+    pub fn columns(&self) -> Vec<&str> {
+        self.parts
+            .iter()
+            .filter_map(|p| match p {
+                Part::Column(c) => Some(c.as_str()),
+                Part::Text(_) => None,
+            })
+            .collect()
+    }
+
+    // render returns path of project, column values are sanitized
+    // so they can not add directories or invalid file names
+    pub fn render(&self, p: &Project) -> PathBuf {
+        let path: String = self
+            .parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(s) => s.clone(),
+                Part::Column(c) => sanitize(&p.get(c).unwrap_or_default()),
+            })
+            .collect();
+        PathBuf::from(path)
+    }
+}
+
+// partition splits storage into storages by rendered template path,
+// different values which are sanitized to the same path are an error
+pub fn partition(
+    storage: &Storage,
+    template: &Template,
+) -> Result<BTreeMap<PathBuf, Storage>, String> {
+    let columns = template.columns();
+    for c in columns.iter() {
+        if storage.column_type(c).is_none() {
+            return Err(format!("unknown column {}", c));
+        }
+    }
+
+    let mut values: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    for p in storage.iter() {
+        let vals: Vec<String> = columns
+            .iter()
+            .map(|c| p.get(c).unwrap_or_default())
+            .collect();
+        let path = template.render(p);
+        match values.get(&path) {
+            Some(other) if *other != vals => {
+                return Err(format!(
+                    "values {:?} and {:?} of {} are both written to {}",
+                    other,
+                    vals,
+                    columns.join(", "),
+                    path.display()
+                ))
+            }
+            Some(_) => {}
+            None => {
+                values.insert(path, vals);
+            }
+        }
+    }
+    Ok(storage.split_by(|p| template.render(p)))
+}
+
+// sanitize replaces path separators, control and reserved characters
+// with _, empty values and dot names are replaced with _
+fn sanitize(value: &str) -> String {
+    let s: String = value
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let s = s.trim_end_matches(['.', ' ']);
+    if s.is_empty() || s.starts_with('.') {
+        format!("_{}", s)
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::*;
+
+    #[test]
+    fn test_partition() {
+        let mut storage = Storage::new(vec![]);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+        let mut p = get_record(2);
+        p[0] = "3";
+        p[3] = "Office/supplies";
+        storage.insert(p).unwrap();
+
+        let template = Template::parse("out/{Category}-{ Currency }.tsv").unwrap();
+        assert_eq!(template.columns(), vec!["Category", "Currency"]);
+        let parts = partition(&storage, &template).unwrap();
+        assert_eq!(
+            parts
+                .iter()
+                .map(|(k, v)| (k.to_str().unwrap(), v.size()))
+                .collect::<Vec<(&str, usize)>>(),
+            vec![
+                ("out/Dairy-EUR.tsv", 1),
+                ("out/Dairy-_.tsv", 1),
+                ("out/Office_supplies-EUR.tsv", 1)
+            ]
+        );
+        assert_eq!(
+            parts[&PathBuf::from("out/Dairy-EUR.tsv")].get_columns(),
+            storage.get_columns()
+        );
+
+        // values must not be merged into one file by sanitizing
+        let mut p = get_record(1);
+        p[0] = "4";
+        p[3] = "Office_supplies";
+        storage.insert(p).unwrap();
+        match partition(&storage, &Template::parse("out/{Category}.tsv").unwrap()) {
+            Err(err) => assert_eq!(
+                err,
+                r#"values ["Office/supplies"] and ["Office_supplies"] of Category are both written to out/Office_supplies.tsv"#
+            ),
+            Ok(_) => panic!("expected collision error"),
+        }
+
+        assert!(partition(&storage, &Template::parse("{Unknown}.tsv").unwrap()).is_err());
+        assert!(Template::parse("out.tsv").is_err());
+        assert!(Template::parse("out/{Category.tsv").is_err());
+        assert_eq!(sanitize(".."), "_");
+        assert_eq!(sanitize(".hidden"), "_.hidden");
+        assert_eq!(sanitize(" a\tb. "), "a_b");
+    }
+}
@@ -0,0 +1,188 @@
+use project::{Project, Type, Value};
+use std::collections::BTreeMap;
+use storage::Storage;
+use table::Table;
+
+const TOTAL: &str = "Total";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    Count,
+    Sum,
+    Mean,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    pub fn parse(s: &str) -> Result<Aggregate, String> {
+        match s {
+            "count" => Ok(Aggregate::Count),
+            "sum" => Ok(Aggregate::Sum),
+            "mean" => Ok(Aggregate::Mean),
+            "min" => Ok(Aggregate::Min),
+            "max" => Ok(Aggregate::Max),
+            _ => Err(format!(
+                "unknown aggregate {}, expected count, sum, mean, min or max",
+                s
+            )),
+        }
+    }
+
+    // apply aggregates numbers of cell, count counts projects
+    // with values, empty cells of other aggregates are empty
+    fn apply(self, values: &[f64]) -> String {
+        let v = match self {
+            Aggregate::Count => return values.len().to_string(),
+            _ if values.is_empty() => return String::new(),
+            Aggregate::Sum => values.iter().sum(),
+            Aggregate::Mean => values.iter().sum::<f64>() / values.len() as f64,
+            Aggregate::Min => values.iter().cloned().fold(f64::INFINITY, f64::min),
+            Aggregate::Max => values.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        };
+        format!("{:.2}", v)
+    }
+}
+
+// Pivot describes pivot table of projects: one row per value of rows column,
+// one column per value of columns column and cells aggregating values column
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pivot {
+    pub rows: String,
+    pub columns: String,
+    // numeric column to aggregate, projects are counted if None
+    pub values: Option<String>,
+    pub aggregate: Aggregate,
+}
+
+impl Pivot {
+    // table returns pivot table of storage with Total row and column,
+    // totals aggregate all values of their row or column, keys are
+    // ordered by typed value, null values are empty keys
+    pub fn table(&self, storage: &Storage) -> Result<Table, String> {
+        for c in [&self.rows, &self.columns] {
+            if storage.column_type(c).is_none() {
+                return Err(format!("unknown column {}", c));
+            }
+        }
+        match &self.values {
+            Some(c) => match storage.column_type(c) {
+                Some(Type::Number) => {}
+                Some(t) => return Err(format!("column {} of type {} can not be aggregated", c, t)),
+                None => return Err(format!("unknown column {}", c)),
+            },
+            None if self.aggregate != Aggregate::Count => {
+                return Err("aggregate requires values column".to_string())
+            }
+            None => {}
+        }
+
+        let key = |p: &Project, c: &str| p.value_of(c).unwrap_or(Value::Null);
+        let mut cells: BTreeMap<(Value, Value), Vec<f64>> = BTreeMap::new();
+        let mut rows: BTreeMap<Value, Vec<f64>> = BTreeMap::new();
+        let mut columns: BTreeMap<Value, Vec<f64>> = BTreeMap::new();
+        let mut total = vec![];
+        for p in storage.iter() {
+            let (r, c) = (key(p, &self.rows), key(p, &self.columns));
+            let cell = cells.entry((r.clone(), c.clone())).or_default();
+            rows.entry(r).or_default();
+            columns.entry(c).or_default();
+
+            let v = match &self.values {
+                Some(values) => match p.value_of(values) {
+                    Some(Value::Number(v)) => v,
+                    _ => continue,
+                },
+                // projects are counted
+                None => 0.0,
+            };
+            cell.push(v);
+            total.push(v);
+        }
+        for ((r, c), values) in cells.iter() {
+            rows.get_mut(r).unwrap().extend(values);
+            columns.get_mut(c).unwrap().extend(values);
+        }
+
+        let mut header = vec![self.rows.clone()];
+        header.extend(columns.keys().map(|c| c.to_string()));
+        header.push(TOTAL.to_string());
+        let mut table = Table::new(header);
+
+        let empty = vec![];
+        for (r, row_values) in rows.iter() {
+            let mut row = vec![r.to_string()];
+            for c in columns.keys() {
+                let values = cells.get(&(r.clone(), c.clone())).unwrap_or(&empty);
+                row.push(self.aggregate.apply(values));
+            }
+            row.push(self.aggregate.apply(row_values));
+            table.rows.push(row);
+        }
+
+        let mut row = vec![TOTAL.to_string()];
+        row.extend(columns.values().map(|values| self.aggregate.apply(values)));
+        row.push(self.aggregate.apply(&total));
+        table.rows.push(row);
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::*;
+
+    #[test]
+    fn test_pivot() {
+        let mut storage = Storage::new(vec![]);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+        let mut p = get_record(2);
+        p[0] = "3";
+        p[3] = "Office supplies";
+        p[5] = "100";
+        p[7] = "Simple";
+        storage.insert(p).unwrap();
+
+        let mut pivot = Pivot {
+            rows: "Category".to_string(),
+            columns: "Complexity".to_string(),
+            values: None,
+            aggregate: Aggregate::Count,
+        };
+        let table = pivot.table(&storage).unwrap();
+        assert_eq!(
+            table.columns,
+            vec!["Category", "Moderate", "Simple", "Total"]
+        );
+        assert_eq!(
+            table.rows,
+            vec![
+                vec!["Dairy", "1", "1", "2"],
+                vec!["Office supplies", "0", "1", "1"],
+                vec!["Total", "1", "2", "3"],
+            ]
+        );
+
+        pivot.values = Some("Savings amount".to_string());
+        pivot.aggregate = Aggregate::Mean;
+        assert_eq!(
+            pivot.table(&storage).unwrap().rows,
+            vec![
+                vec!["Dairy", "141415.94", "", "141415.94"],
+                vec!["Office supplies", "", "100.00", "100.00"],
+                vec!["Total", "141415.94", "100.00", "70757.97"],
+            ]
+        );
+
+        pivot.values = Some("Category".to_string());
+        assert!(pivot.table(&storage).is_err());
+        pivot.values = None;
+        assert!(pivot.table(&storage).is_err());
+        pivot.rows = "Unknown".to_string();
+        pivot.aggregate = Aggregate::Count;
+        assert!(pivot.table(&storage).is_err());
+        assert!(Aggregate::parse("median").is_err());
+    }
+}
@@ -0,0 +1,1029 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use expr;
+use project::{Project, Type, Value, DATE_FORMAT};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::iter::Peekable;
+use std::slice;
+use std::str::Chars;
+use storage::Storage;
+use table::Table;
+
+// name of table of input file in queries
+pub const INPUT: &str = "input";
+
+const KEYWORDS: [&str; 17] = [
+    "SELECT", "FROM", "WHERE", "GROUP", "BY", "ORDER", "ASC", "DESC", "LIMIT", "OFFSET", "AND",
+    "OR", "NOT", "LIKE", "IS", "NULL", "AS",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn parse(name: &str) -> Option<Aggregate> {
+        match name.to_lowercase().as_str() {
+            "count" => Some(Aggregate::Count),
+            "sum" => Some(Aggregate::Sum),
+            "avg" => Some(Aggregate::Avg),
+            "min" => Some(Aggregate::Min),
+            "max" => Some(Aggregate::Max),
+            _ => None,
+        }
+    }
+
+    // apply aggregates values without nulls
+    fn apply(self, values: Vec<Value>) -> Value {
+        let numbers = || {
+            values.iter().filter_map(|v| match v {
+                Value::Number(n) => Some(*n),
+                _ => None,
+            })
+        };
+        match self {
+            Aggregate::Count => Value::Number(values.len() as f64),
+            _ if values.is_empty() => Value::Null,
+            Aggregate::Sum => Value::Number(numbers().sum()),
+            Aggregate::Avg => Value::Number(numbers().sum::<f64>() / values.len() as f64),
+            Aggregate::Min => values.into_iter().min().unwrap_or(Value::Null),
+            Aggregate::Max => values.into_iter().max().unwrap_or(Value::Null),
+        }
+    }
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_lowercase())
+    }
+}
+
+// Expr is an expression of query over project columns, conditions
+// give 1 if true, 0 if false and Null if unknown
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Column(String),
+    Literal(Value),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    // arithmetic operation +, -, *, / as in derived columns
+    Arith(char, Box<Expr>, Box<Expr>),
+    // comparison =, <>, <, <=, >, >=
+    Compare(&'static str, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    // case-insensitive match with % and _ wildcards
+    Like(Box<Expr>, String),
+    IsNull(Box<Expr>),
+    Call(String, Vec<Expr>),
+    // aggregate of group, count(*) has no argument
+    Aggregate(Aggregate, Option<Box<Expr>>),
+}
+
+// Item is a selected expression with name of output column
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub expr: Expr,
+    pub name: String,
+}
+
+// Query is a select statement over projects of input file, e.g.
+// `SELECT Category, sum("Savings amount") FROM input WHERE Complexity <> 'Simple'
+// GROUP BY Category ORDER BY 2 DESC LIMIT 10`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    // selected items, all columns if empty (`SELECT *`)
+    pub select: Vec<Item>,
+    pub filter: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    // expressions, aliases or positions of selected items,
+    // in descending order if set
+    pub order_by: Vec<(Expr, bool)>,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+impl Query {
+    pub fn parse(s: &str) -> Result<Query, String> {
+        let mut p = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        let q = p.query()?;
+        if let Some(t) = p.peek() {
+            return Err(format!("unexpected {:?}", t));
+        }
+        Ok(q)
+    }
+
+    // run plans query for columns of storage and returns table of results,
+    // groups are ordered by their keys and projects keep storage order
+    // unless ordered otherwise
+    pub fn run(&self, storage: &Storage) -> Result<Table, String> {
+        let plan = self.plan(storage)?;
+
+        let mut projects = vec![];
+        for p in storage.iter() {
+            let row = slice::from_ref(&p);
+            match &plan.filter {
+                Some(f) if truth(&f.eval(row)) != Some(true) => {}
+                _ => projects.push(p),
+            }
+        }
+
+        let groups: Vec<Vec<&Project>> = if plan.aggregated {
+            let mut groups: BTreeMap<Vec<Value>, Vec<&Project>> = BTreeMap::new();
+            for p in projects {
+                let row = slice::from_ref(&p);
+                let key = plan.group_by.iter().map(|e| e.eval(row)).collect();
+                groups.entry(key).or_default().push(p);
+            }
+            // aggregates without groups give one row
+            if groups.is_empty() && plan.group_by.is_empty() {
+                vec![vec![]]
+            } else {
+                groups.into_values().collect()
+            }
+        } else {
+            projects.into_iter().map(|p| vec![p]).collect()
+        };
+
+        let mut rows: Vec<(Vec<Value>, Vec<Value>)> = groups
+            .iter()
+            .map(|g| {
+                let keys = plan.order_by.iter().map(|(e, _)| e.eval(g)).collect();
+                let values = plan.items.iter().map(|i| i.expr.eval(g)).collect();
+                (keys, values)
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            for (i, (_, desc)) in plan.order_by.iter().enumerate() {
+                let o = a.0[i].cmp(&b.0[i]);
+                let o = if *desc { o.reverse() } else { o };
+                if o != Ordering::Equal {
+                    return o;
+                }
+            }
+            Ordering::Equal
+        });
+
+        let mut table = Table::new(plan.items.iter().map(|i| i.name.clone()).collect());
+        table.rows = rows
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .map(|(_, values)| {
+                values
+                    .iter()
+                    .zip(plan.items.iter())
+                    .map(|(v, item)| format_value(v, item.expr.has_aggregate()))
+                    .collect()
+            })
+            .collect();
+        Ok(table)
+    }
+
+    // plan checks expressions against columns of storage and resolves
+    // selected columns and order by aliases and positions
+    fn plan(&self, storage: &Storage) -> Result<Plan, String> {
+        let schema = |c: &str| storage.column_type(c);
+
+        let mut items = self.select.clone();
+        if items.is_empty() {
+            items = storage
+                .get_columns()
+                .iter()
+                .map(|c| Item {
+                    expr: Expr::Column(c.to_string()),
+                    name: c.to_string(),
+                })
+                .collect();
+        }
+        for i in items.iter_mut() {
+            i.expr.check(&schema, true)?;
+        }
+
+        let mut filter = self.filter.clone();
+        if let Some(f) = filter.as_mut() {
+            f.check(&schema, false)
+                .map_err(|err| format!("{} in WHERE", err))?;
+        }
+        let mut group_by = vec![];
+        for e in self.group_by.iter() {
+            let mut e = resolve(e, &items, &schema)?;
+            e.check(&schema, false)
+                .map_err(|err| format!("{} in GROUP BY", err))?;
+            group_by.push(e);
+        }
+        let mut order_by = vec![];
+        for (e, desc) in self.order_by.iter() {
+            let mut e = resolve(e, &items, &schema)?;
+            e.check(&schema, true)?;
+            order_by.push((e, *desc));
+        }
+
+        let aggregated = !group_by.is_empty()
+            || items.iter().any(|i| i.expr.has_aggregate())
+            || order_by.iter().any(|(e, _)| e.has_aggregate());
+        if aggregated {
+            let exprs = items
+                .iter()
+                .map(|i| &i.expr)
+                .chain(order_by.iter().map(|(e, _)| e));
+            for e in exprs {
+                if let Some(c) = e.ungrouped(&group_by) {
+                    return Err(format!(
+                        "column {} must be in GROUP BY or used in aggregate",
+                        c
+                    ));
+                }
+            }
+        }
+
+        Ok(Plan {
+            items,
+            filter,
+            group_by,
+            order_by,
+            aggregated,
+        })
+    }
+}
+
+// resolve replaces position or alias of selected item with its expression
+fn resolve<F: Fn(&str) -> Option<Type>>(
+    e: &Expr,
+    items: &[Item],
+    schema: &F,
+) -> Result<Expr, String> {
+    match e {
+        Expr::Literal(Value::Number(n)) => match items.get((*n as usize).wrapping_sub(1)) {
+            Some(i) if n.fract() == 0.0 => Ok(i.expr.clone()),
+            _ => Err(format!("position {} is not selected", n)),
+        },
+        Expr::Column(c) if schema(c).is_none() => match items.iter().find(|i| &i.name == c) {
+            Some(i) => Ok(i.expr.clone()),
+            None => Err(format!("unknown column {}", c)),
+        },
+        e => Ok(e.clone()),
+    }
+}
+
+// Plan is a query checked against columns of storage
+struct Plan {
+    items: Vec<Item>,
+    filter: Option<Expr>,
+    group_by: Vec<Expr>,
+    order_by: Vec<(Expr, bool)>,
+    aggregated: bool,
+}
+
+impl Expr {
+    // check returns type of expression with types of columns from schema,
+    // string literals compared with dates are parsed as dates
+    fn check<F: Fn(&str) -> Option<Type>>(
+        &mut self,
+        schema: &F,
+        aggregate: bool,
+    ) -> Result<Type, String> {
+        match self {
+            Expr::Column(c) => schema(c).ok_or_else(|| format!("unknown column {}", c)),
+            Expr::Literal(v) => Ok(v.get_type().unwrap_or(Type::Str)),
+            Expr::Neg(e) => match e.check(schema, aggregate)? {
+                Type::Number => Ok(Type::Number),
+                t => Err(format!("can't negate {}", t)),
+            },
+            Expr::Not(e) | Expr::IsNull(e) => e.check(schema, aggregate).map(|_| Type::Number),
+            Expr::And(l, r) | Expr::Or(l, r) => {
+                l.check(schema, aggregate)?;
+                r.check(schema, aggregate)?;
+                Ok(Type::Number)
+            }
+            Expr::Arith(op, l, r) => expr::binary_type(
+                *op,
+                l.check(schema, aggregate)?,
+                r.check(schema, aggregate)?,
+            ),
+            Expr::Compare(op, l, r) => {
+                let (lt, rt) = (l.check(schema, aggregate)?, r.check(schema, aggregate)?);
+                match (lt, rt) {
+                    (Type::Date, Type::Str) => r.parse_date()?,
+                    (Type::Str, Type::Date) => l.parse_date()?,
+                    (lt, rt) if lt != rt => {
+                        return Err(format!("can't compare {} {} {}", lt, op, rt))
+                    }
+                    _ => {}
+                }
+                Ok(Type::Number)
+            }
+            Expr::Like(e, _) => match e.check(schema, aggregate)? {
+                Type::Str => Ok(Type::Number),
+                t => Err(format!("can't match {} with LIKE", t)),
+            },
+            Expr::Call(name, args) => {
+                let args = args
+                    .iter_mut()
+                    .map(|a| a.check(schema, aggregate))
+                    .collect::<Result<Vec<Type>, String>>()?;
+                expr::call_type(name, &args)
+            }
+            Expr::Aggregate(f, _) if !aggregate => Err(format!("aggregate {} is not allowed", f)),
+            Expr::Aggregate(_, None) => Ok(Type::Number),
+            Expr::Aggregate(f, Some(e)) => match (*f, e.check(schema, false)?) {
+                (Aggregate::Count, _) => Ok(Type::Number),
+                (Aggregate::Min, t) | (Aggregate::Max, t) => Ok(t),
+                (_, Type::Number) => Ok(Type::Number),
+                (f, t) => Err(format!("can't apply {} to {}", f, t)),
+            },
+        }
+    }
+
+    // parse_date replaces string literal with date
+    fn parse_date(&mut self) -> Result<(), String> {
+        if let Expr::Literal(Value::Str(s)) = self {
+            let d = NaiveDateTime::parse_from_str(s, DATE_FORMAT)
+                .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S"))
+                .or_else(|_| {
+                    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                        .map(|d| d.and_hms_opt(0, 0, 0).unwrap())
+                })
+                .map_err(|_| format!("date parse error {}", s))?;
+            *self = Expr::Literal(Value::Date(d));
+            return Ok(());
+        }
+        Err("can't compare date with string".to_string())
+    }
+
+    fn has_aggregate(&self) -> bool {
+        match self {
+            Expr::Aggregate(..) => true,
+            Expr::Column(_) | Expr::Literal(_) => false,
+            Expr::Neg(e) | Expr::Not(e) | Expr::IsNull(e) | Expr::Like(e, _) => e.has_aggregate(),
+            Expr::Arith(_, l, r) | Expr::Compare(_, l, r) | Expr::And(l, r) | Expr::Or(l, r) => {
+                l.has_aggregate() || r.has_aggregate()
+            }
+            Expr::Call(_, args) => args.iter().any(|a| a.has_aggregate()),
+        }
+    }
+
+    // ungrouped returns column of expression which is neither
+    // group key nor aggregated
+    fn ungrouped(&self, keys: &[Expr]) -> Option<&str> {
+        if keys.contains(self) {
+            return None;
+        }
+        match self {
+            Expr::Column(c) => Some(c),
+            Expr::Literal(_) | Expr::Aggregate(..) => None,
+            Expr::Neg(e) | Expr::Not(e) | Expr::IsNull(e) | Expr::Like(e, _) => e.ungrouped(keys),
+            Expr::Arith(_, l, r) | Expr::Compare(_, l, r) | Expr::And(l, r) | Expr::Or(l, r) => {
+                l.ungrouped(keys).or_else(|| r.ungrouped(keys))
+            }
+            Expr::Call(_, args) => args.iter().find_map(|a| a.ungrouped(keys)),
+        }
+    }
+
+    // eval evaluates expression for group of projects, columns are
+    // values of the first project, a single project is a group of one
+    fn eval(&self, group: &[&Project]) -> Value {
+        match self {
+            Expr::Column(c) => group
+                .first()
+                .and_then(|p| p.value_of(c))
+                .unwrap_or(Value::Null),
+            Expr::Literal(v) => v.clone(),
+            Expr::Neg(e) => match e.eval(group) {
+                Value::Number(n) => Value::Number(-n),
+                _ => Value::Null,
+            },
+            Expr::Not(e) => boolean(truth(&e.eval(group)).map(|b| !b)),
+            Expr::And(l, r) => match (truth(&l.eval(group)), truth(&r.eval(group))) {
+                (Some(false), _) | (_, Some(false)) => boolean(Some(false)),
+                (Some(true), Some(true)) => boolean(Some(true)),
+                _ => Value::Null,
+            },
+            Expr::Or(l, r) => match (truth(&l.eval(group)), truth(&r.eval(group))) {
+                (Some(true), _) | (_, Some(true)) => boolean(Some(true)),
+                (Some(false), Some(false)) => boolean(Some(false)),
+                _ => Value::Null,
+            },
+            Expr::Arith(op, l, r) => expr::binary(*op, l.eval(group), r.eval(group)),
+            Expr::Compare(op, l, r) => match (l.eval(group), r.eval(group)) {
+                (Value::Null, _) | (_, Value::Null) => Value::Null,
+                (a, b) => {
+                    let o = a.cmp(&b);
+                    boolean(Some(match *op {
+                        "=" => o == Ordering::Equal,
+                        "<>" => o != Ordering::Equal,
+                        "<" => o == Ordering::Less,
+                        "<=" => o != Ordering::Greater,
+                        ">" => o == Ordering::Greater,
+                        _ => o != Ordering::Less,
+                    }))
+                }
+            },
+            Expr::Like(e, pattern) => match e.eval(group) {
+                Value::Str(s) => {
+                    let s: Vec<char> = s.to_lowercase().chars().collect();
+                    let p: Vec<char> = pattern.to_lowercase().chars().collect();
+                    boolean(Some(like(&s, &p)))
+                }
+                _ => Value::Null,
+            },
+            Expr::IsNull(e) => boolean(Some(e.eval(group) == Value::Null)),
+            Expr::Call(name, args) => {
+                let args: Vec<Value> = args.iter().map(|a| a.eval(group)).collect();
+                expr::call(name, &args)
+            }
+            Expr::Aggregate(f, e) => {
+                let values = match e {
+                    Some(e) => group
+                        .iter()
+                        .map(|p| e.eval(slice::from_ref(p)))
+                        .filter(|v| *v != Value::Null)
+                        .collect(),
+                    None => vec![Value::Null; group.len()],
+                };
+                f.apply(values)
+            }
+        }
+    }
+}
+
+// Expr is printed as name of output column
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Column(c) => write!(f, "{}", c),
+            Expr::Literal(Value::Str(s)) => write!(f, "'{}'", s),
+            Expr::Literal(v) => write!(f, "{}", v),
+            Expr::Neg(e) => write!(f, "-{}", e),
+            Expr::Not(e) => write!(f, "NOT {}", e),
+            Expr::Arith(op, l, r) => write!(f, "{} {} {}", l, op, r),
+            Expr::Compare(op, l, r) => write!(f, "{} {} {}", l, op, r),
+            Expr::And(l, r) => write!(f, "{} AND {}", l, r),
+            Expr::Or(l, r) => write!(f, "{} OR {}", l, r),
+            Expr::Like(e, p) => write!(f, "{} LIKE '{}'", e, p),
+            Expr::IsNull(e) => write!(f, "{} IS NULL", e),
+            Expr::Call(name, args) => {
+                let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+            Expr::Aggregate(a, None) => write!(f, "{}(*)", a),
+            Expr::Aggregate(a, Some(e)) => write!(f, "{}({})", a, e),
+        }
+    }
+}
+
+// format_value returns value as printed in results, fractional numbers
+// of aggregates have 2 decimals like totals of stats and pivot
+fn format_value(v: &Value, aggregate: bool) -> String {
+    match v {
+        Value::Number(n) if aggregate && n.fract() != 0.0 => format!("{:.2}", n),
+        v => v.to_string(),
+    }
+}
+
+fn truth(v: &Value) -> Option<bool> {
+    match v {
+        Value::Null => None,
+        Value::Number(n) => Some(*n != 0.0),
+        Value::Str(s) => Some(!s.is_empty()),
+        Value::Date(_) => Some(true),
+    }
+}
+
+fn boolean(b: Option<bool>) -> Value {
+    match b {
+        Some(b) => Value::Number(if b { 1.0 } else { 0.0 }),
+        None => Value::Null,
+    }
+}
+
+// like matches string with pattern, % matches any characters
+// and _ matches one character. On mismatch only the last % takes one
+// more character, so matching takes at most len(s) * len(p) steps
+fn like(s: &[char], p: &[char]) -> bool {
+    let (mut i, mut j) = (0, 0);
+    // positions of pattern after last % and of string it matched from
+    let mut backtrack: Option<(usize, usize)> = None;
+    while i < s.len() {
+        match p.get(j) {
+            Some('%') => {
+                j += 1;
+                backtrack = Some((j, i));
+            }
+            Some(c) if *c == '_' || *c == s[i] => {
+                i += 1;
+                j += 1;
+            }
+            _ => match backtrack {
+                Some((pj, si)) => {
+                    j = pj;
+                    i = si + 1;
+                    backtrack = Some((pj, si + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    p[j..].iter().all(|c| *c == '%')
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Str(String),
+    Number(f64),
+    Op(&'static str),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        chars.next();
+        let op = match c {
+            ' ' | '\t' | '\n' | '\r' | ';' => continue,
+            '(' => "(",
+            ')' => ")",
+            ',' => ",",
+            '*' => "*",
+            '+' => "+",
+            '-' => "-",
+            '/' => "/",
+            '=' => "=",
+            '<' if eat(&mut chars, '=') => "<=",
+            '<' if eat(&mut chars, '>') => "<>",
+            '<' => "<",
+            '>' if eat(&mut chars, '=') => ">=",
+            '>' => ">",
+            '!' if eat(&mut chars, '=') => "<>",
+            '"' => {
+                tokens.push(Token::Quoted(read_quoted(&mut chars, '"')?));
+                continue;
+            }
+            '\'' => {
+                tokens.push(Token::Str(read_quoted(&mut chars, '\'')?));
+                continue;
+            }
+            '0'..='9' | '.' => {
+                let mut v = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_digit() || c == '.') {
+                        break;
+                    }
+                    v.push(c);
+                    chars.next();
+                }
+                match v.parse() {
+                    Ok(n) => tokens.push(Token::Number(n)),
+                    Err(_) => return Err(format!("invalid number {}", v)),
+                }
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut v = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    v.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(v));
+                continue;
+            }
+            c => return Err(format!("unexpected character {:?}", c)),
+        };
+        tokens.push(Token::Op(op));
+    }
+    Ok(tokens)
+}
+
+fn eat(chars: &mut Peekable<Chars>, c: char) -> bool {
+    if chars.peek() == Some(&c) {
+        chars.next();
+        return true;
+    }
+    false
+}
+
+// read_quoted reads until closing quote, doubled quote is the quote itself
+fn read_quoted(chars: &mut Peekable<Chars>, quote: char) -> Result<String, String> {
+    let mut v = String::new();
+    while let Some(c) = chars.next() {
+        if c == quote && !eat(chars, quote) {
+            return Ok(v);
+        }
+        v.push(c);
+    }
+    Err(format!("missing closing {}", quote))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        match self.peek() {
+            Some(Token::Op(o)) if *o == op => {}
+            _ => return false,
+        }
+        self.pos += 1;
+        true
+    }
+
+    // keyword eats keyword, keywords are case-insensitive
+    fn keyword(&mut self, kw: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case(kw) => {}
+            _ => return false,
+        }
+        self.pos += 1;
+        true
+    }
+
+    fn expect(&mut self, kw: &str) -> Result<(), String> {
+        if self.keyword(kw) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(t) => Err(format!("expected {}, found {:?}", kw, t)),
+            None => Err(format!("expected {}", kw)),
+        }
+    }
+
+    fn query(&mut self) -> Result<Query, String> {
+        self.expect("SELECT")?;
+        let mut select = vec![];
+        if !self.eat("*") {
+            loop {
+                select.push(self.item()?);
+                if !self.eat(",") {
+                    break;
+                }
+            }
+        }
+
+        self.expect("FROM")?;
+        match self.next() {
+            Some(Token::Word(t)) | Some(Token::Quoted(t)) if t.eq_ignore_ascii_case(INPUT) => {}
+            Some(Token::Word(t)) | Some(Token::Quoted(t)) => {
+                return Err(format!("unknown table {}, expected {}", t, INPUT))
+            }
+            _ => return Err(format!("expected table {}", INPUT)),
+        }
+
+        let mut q = Query {
+            select,
+            filter: None,
+            group_by: vec![],
+            order_by: vec![],
+            limit: None,
+            offset: 0,
+        };
+        if self.keyword("WHERE") {
+            q.filter = Some(self.or()?);
+        }
+        if self.keyword("GROUP") {
+            self.expect("BY")?;
+            loop {
+                q.group_by.push(self.or()?);
+                if !self.eat(",") {
+                    break;
+                }
+            }
+        }
+        if self.keyword("ORDER") {
+            self.expect("BY")?;
+            loop {
+                let e = self.or()?;
+                let desc = self.keyword("DESC");
+                if !desc {
+                    self.keyword("ASC");
+                }
+                q.order_by.push((e, desc));
+                if !self.eat(",") {
+                    break;
+                }
+            }
+        }
+        if self.keyword("LIMIT") {
+            q.limit = Some(self.count("LIMIT")?);
+            if self.keyword("OFFSET") {
+                q.offset = self.count("OFFSET")?;
+            }
+        }
+        Ok(q)
+    }
+
+    fn count(&mut self, kw: &str) -> Result<usize, String> {
+        match self.next() {
+            Some(Token::Number(n)) if n.fract() == 0.0 => Ok(n as usize),
+            _ => Err(format!("{} expects number of rows", kw)),
+        }
+    }
+
+    fn item(&mut self) -> Result<Item, String> {
+        let expr = self.or()?;
+        let explicit = self.keyword("AS");
+        let alias = match self.peek().cloned() {
+            Some(Token::Quoted(w)) => Some(w),
+            Some(Token::Word(w)) if !is_keyword(&w) => Some(w),
+            _ if explicit => return Err("AS expects name".to_string()),
+            _ => None,
+        };
+        if alias.is_some() {
+            self.pos += 1;
+        }
+        let name = alias.unwrap_or_else(|| expr.to_string());
+        Ok(Item { expr, name })
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while self.keyword("OR") {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.not()?;
+        while self.keyword("AND") {
+            left = Expr::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.keyword("NOT") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.sum()?;
+        if let Some(Token::Op(op)) = self.peek().cloned() {
+            if ["=", "<>", "<", "<=", ">", ">="].contains(&op) {
+                self.pos += 1;
+                return Ok(Expr::Compare(op, Box::new(left), Box::new(self.sum()?)));
+            }
+        }
+        if self.keyword("IS") {
+            let not = self.keyword("NOT");
+            self.expect("NULL")?;
+            let e = Expr::IsNull(Box::new(left));
+            return Ok(if not { Expr::Not(Box::new(e)) } else { e });
+        }
+        let not = self.keyword("NOT");
+        if self.keyword("LIKE") {
+            let e = match self.next() {
+                Some(Token::Str(p)) => Expr::Like(Box::new(left), p),
+                _ => return Err("LIKE expects string pattern".to_string()),
+            };
+            return Ok(if not { Expr::Not(Box::new(e)) } else { e });
+        }
+        if not {
+            return Err("expected LIKE after NOT".to_string());
+        }
+        Ok(left)
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut left = self.product()?;
+        loop {
+            let op = if self.eat("+") {
+                '+'
+            } else if self.eat("-") {
+                '-'
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Arith(op, Box::new(left), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut left = self.factor()?;
+        loop {
+            let op = if self.eat("*") {
+                '*'
+            } else if self.eat("/") {
+                '/'
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Arith(op, Box::new(left), Box::new(self.factor()?));
+        }
+    }
+
+    fn factor(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Op("-")) => Ok(Expr::Neg(Box::new(self.factor()?))),
+            Some(Token::Op("(")) => {
+                let e = self.or()?;
+                if !self.eat(")") {
+                    return Err("missing closing )".to_string());
+                }
+                Ok(e)
+            }
+            Some(Token::Number(n)) => Ok(Expr::Literal(Value::Number(n))),
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::Str(s))),
+            Some(Token::Quoted(c)) => Ok(Expr::Column(c)),
+            Some(Token::Word(w)) if self.eat("(") => self.call(w),
+            Some(Token::Word(w)) if !is_keyword(&w) => Ok(Expr::Column(w)),
+            Some(t) => Err(format!("unexpected {:?}", t)),
+            None => Err("unexpected end of query".to_string()),
+        }
+    }
+
+    fn call(&mut self, name: String) -> Result<Expr, String> {
+        let aggregate = Aggregate::parse(&name);
+        if aggregate == Some(Aggregate::Count) && self.eat("*") {
+            if !self.eat(")") {
+                return Err("missing closing ) of count".to_string());
+            }
+            return Ok(Expr::Aggregate(Aggregate::Count, None));
+        }
+
+        let mut args = vec![];
+        if !self.eat(")") {
+            loop {
+                args.push(self.or()?);
+                if self.eat(")") {
+                    break;
+                }
+                if !self.eat(",") {
+                    return Err(format!("missing closing ) of {}", name));
+                }
+            }
+        }
+        match aggregate {
+            Some(a) if args.len() == 1 => Ok(Expr::Aggregate(a, Some(Box::new(args.remove(0))))),
+            Some(a) => Err(format!("{} expects one argument", a)),
+            None => Ok(Expr::Call(name.to_lowercase(), args)),
+        }
+    }
+}
+
+fn is_keyword(w: &str) -> bool {
+    KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(w))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::*;
+
+    fn storage() -> Storage {
+        let mut p3 = get_record(2);
+        p3[0] = "3";
+        p3[3] = "Office supplies";
+        p3[4] = "Clark Kent";
+        p3[5] = "100";
+        p3[7] = "Hazardous";
+        let mut p4 = get_record(2);
+        p4[0] = "4";
+        p4[5] = "50.5";
+        get_storage(vec![get_record(1), get_record(2), p3, p4])
+    }
+
+    fn run(sql: &str) -> Result<Table, String> {
+        Query::parse(sql)?.run(&storage())
+    }
+
+    #[test]
+    fn test_parse() {
+        let q = Query::parse(
+            "select Category, sum(\"Savings amount\") as total from input \
+             where not Complexity = 'Simple' group by Category order by 2 desc limit 5 offset 1",
+        )
+        .unwrap();
+        assert_eq!(q.select[1].name, "total");
+        assert_eq!(
+            q.select[1].expr,
+            Expr::Aggregate(
+                Aggregate::Sum,
+                Some(Box::new(Expr::Column("Savings amount".to_string())))
+            )
+        );
+        assert_eq!(q.group_by, vec![Expr::Column("Category".to_string())]);
+        assert_eq!(q.order_by, vec![(Expr::Literal(Value::Number(2.0)), true)]);
+        assert_eq!((q.limit, q.offset), (Some(5), 1));
+        assert_eq!(Query::parse("SELECT * FROM input").unwrap().select, vec![]);
+
+        assert!(Query::parse("SELECT Category FROM projects").is_err());
+        assert!(Query::parse("SELECT Category").is_err());
+        assert!(Query::parse("SELECT sum(Category, Project) FROM input").is_err());
+        assert!(Query::parse("SELECT Category FROM input WHERE 'abc").is_err());
+        assert!(Query::parse("SELECT Category FROM input LIMIT -1").is_err());
+    }
+
+    #[test]
+    fn test_run() {
+        let t = run(
+            "SELECT Category, sum(\"Savings amount\"), count(*) FROM input \
+                     WHERE Complexity <> 'Simple' GROUP BY Category ORDER BY 2 DESC",
+        )
+        .unwrap();
+        assert_eq!(
+            t.columns,
+            vec!["Category", "sum(Savings amount)", "count(*)"]
+        );
+        assert_eq!(
+            t.rows,
+            vec![
+                vec!["Dairy", "141466.44", "2"],
+                vec!["Office supplies", "100", "1"],
+            ]
+        );
+
+        let t = run("SELECT Project AS id, \"Savings amount\" / 10 FROM input \
+                     WHERE \"Savings amount\" IS NOT NULL AND (Description LIKE '%CRÈME%' OR Project = '3') \
+                     AND \"Start date\" >= '2013-01-01' ORDER BY id DESC LIMIT 2")
+        .unwrap();
+        assert_eq!(t.columns, vec!["id", "Savings amount / 10"]);
+        assert_eq!(t.rows, vec![vec!["4", "5.05"], vec!["3", "10"]]);
+
+        let t = run("SELECT count(*), max(\"Start date\"), avg(\"Savings amount\") FROM input WHERE Project = '9'")
+            .unwrap();
+        assert_eq!(t.rows, vec![vec!["0", "", ""]]);
+        let t = run(
+            "SELECT avg(\"Savings amount\"), sum(\"Savings amount\") / 3 FROM input \
+                     WHERE Project = '3' OR Project = '4'",
+        )
+        .unwrap();
+        assert_eq!(t.rows, vec![vec!["75.25", "50.17"]]);
+        let t =
+            run("SELECT year(\"Start date\") y, count(*) FROM input GROUP BY y ORDER BY y DESC")
+                .unwrap();
+        assert_eq!(t.rows, vec![vec!["2014", "1"], vec!["2013", "3"]]);
+        let t =
+            run("SELECT year(\"Start date\"), count(*) FROM input GROUP BY year(\"Start date\")")
+                .unwrap();
+        assert_eq!(t.columns, vec!["year(Start date)", "count(*)"]);
+        assert_eq!(t.rows, vec![vec!["2013", "3"], vec!["2014", "1"]]);
+        assert_eq!(run("SELECT * FROM input LIMIT 1").unwrap().columns.len(), 8);
+        // dates out of range are NULL
+        let t = run("SELECT \"Start date\" + 100000000000 FROM input LIMIT 1").unwrap();
+        assert_eq!(t.rows, vec![vec![""]]);
+
+        assert_eq!(
+            run("SELECT Category, Project FROM input GROUP BY Category").unwrap_err(),
+            "column Project must be in GROUP BY or used in aggregate"
+        );
+        assert!(run("SELECT Category FROM input WHERE count(*) > 1").is_err());
+        assert!(run("SELECT sum(Category) FROM input").is_err());
+        assert!(run("SELECT Category FROM input WHERE Project = 3").is_err());
+        assert!(run("SELECT Category FROM input ORDER BY 3").is_err());
+        assert!(run("SELECT Unknown FROM input").is_err());
+    }
+
+    #[test]
+    fn test_like() {
+        let like = |s: &str, p: &str| {
+            let s: Vec<char> = s.chars().collect();
+            let p: Vec<char> = p.chars().collect();
+            super::like(&s, &p)
+        };
+        assert!(like("Dairy", "Dairy"));
+        assert!(like("Dairy", "D%"));
+        assert!(like("Dairy", "%ry"));
+        assert!(like("Dairy", "%i%"));
+        assert!(like("Dairy", "D_i_y"));
+        assert!(like("", "%%"));
+        assert!(like("abcbc", "%bc"));
+        assert!(like("mississippi", "m%iss%pi"));
+        assert!(!like("Dairy", "Dairy_"));
+        assert!(!like("Dairy", "%x%"));
+        assert!(!like("Dairy", "_"));
+        assert!(!like("", "_"));
+
+        // many % do not take exponential time
+        let s = "a".repeat(100);
+        let p = format!("{}b", "%a".repeat(30));
+        assert!(!like(&s, &p));
+    }
+}
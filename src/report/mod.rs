@@ -0,0 +1,166 @@
+use project::{Project, Value};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use storage::Storage;
+
+pub const TITLE: &str = "Projects";
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }
+th { background: #1f4e78; color: #fff; cursor: pointer; user-select: none; }
+td.number { text-align: right; }
+tfoot td { font-weight: bold; background: #eee; }";
+
+// sorts rows of table body by clicked header, again in reverse,
+// numbers by value and other cells as text
+const SCRIPT: &str = "document.querySelectorAll('th').forEach(function (th) {
+  th.addEventListener('click', function () {
+    var table = th.closest('table'), body = table.tBodies[0];
+    var i = th.cellIndex, desc = th.dataset.order === 'asc';
+    th.dataset.order = desc ? 'desc' : 'asc';
+    var value = function (row) {
+      var cell = row.cells[i];
+      return cell.dataset.value !== undefined ? parseFloat(cell.dataset.value) : cell.textContent;
+    };
+    Array.from(body.rows).sort(function (a, b) {
+      var x = value(a), y = value(b);
+      var c = x < y ? -1 : x > y ? 1 : 0;
+      return desc ? -c : c;
+    }).forEach(function (row) { body.appendChild(row); });
+  });
+});";
+
+// write_html writes columns of projects as standalone html report,
+// one sortable table per category with totals of projects and
+// savings amounts by currency in footer, and totals of all projects
+pub fn write_html<W: Write>(mut writer: W, storage: &Storage, columns: &[&str]) -> io::Result<()> {
+    let groups = storage.group_by("Category").map_err(io::Error::other)?;
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(writer, "<title>{}</title>", TITLE)?;
+    writeln!(writer, "<style>\n{}\n</style>\n</head>\n<body>", STYLE)?;
+    writeln!(writer, "<h1>{}</h1>", TITLE)?;
+    for (category, projects) in groups.iter() {
+        writeln!(writer, "<section>\n<h2>{}</h2>", escape(category))?;
+        write_projects(&mut writer, columns, projects)?;
+        writeln!(writer, "</section>")?;
+    }
+    let all: Vec<&Project> = storage.iter().collect();
+    writeln!(
+        writer,
+        "<p><strong>Total:</strong> {}</p>",
+        totals(&all).join(", ")
+    )?;
+    writeln!(writer, "<script>\n{}\n</script>\n</body>\n</html>", SCRIPT)?;
+    writer.flush()
+}
+
+// escape escapes html special characters of text
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_projects<W: Write>(
+    writer: &mut W,
+    columns: &[&str],
+    projects: &[&Project],
+) -> io::Result<()> {
+    writeln!(writer, "<table>\n<thead>\n<tr>")?;
+    for c in columns.iter() {
+        writeln!(writer, "<th>{}</th>", escape(c))?;
+    }
+    writeln!(writer, "</tr>\n</thead>\n<tbody>")?;
+    for p in projects.iter() {
+        writeln!(writer, "<tr>")?;
+        for c in columns.iter() {
+            let v = escape(&p.get(c).unwrap_or_default());
+            match p.value_of(c) {
+                // sorted by value, not by printed text
+                Some(Value::Number(n)) => writeln!(
+                    writer,
+                    "<td class=\"number\" data-value=\"{}\">{}</td>",
+                    n, v
+                )?,
+                _ => writeln!(writer, "<td>{}</td>", v)?,
+            }
+        }
+        writeln!(writer, "</tr>")?;
+    }
+    writeln!(writer, "</tbody>\n<tfoot>\n<tr>")?;
+    writeln!(
+        writer,
+        "<td colspan=\"{}\">{}</td>",
+        columns.len().max(1),
+        totals(projects).join(", ")
+    )?;
+    writeln!(writer, "</tr>\n</tfoot>\n</table>")
+}
+
+// totals returns number of projects and their savings amount totals
+// by currency as printed in report
+fn totals(projects: &[&Project]) -> Vec<String> {
+    let mut amounts: BTreeMap<String, f64> = BTreeMap::new();
+    for p in projects.iter() {
+        if let (Some(amount), Some(currency)) = (&p.savings_amount, &p.currency) {
+            *amounts.entry(currency.to_string()).or_default() += amount.value();
+        }
+    }
+    let mut totals = vec![format!("{} projects", projects.len())];
+    totals.extend(
+        amounts
+            .iter()
+            .map(|(currency, total)| format!("{:.2} {} savings", total, escape(currency))),
+    );
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str;
+    use test_util::*;
+
+    #[test]
+    fn test_write_html() {
+        let mut storage = Storage::new(vec![]);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+        let mut p = get_project(2);
+        p.category = "Bakery".to_string();
+        p.description = "Replace <b>butter</b> & \"cream\"".to_string();
+        storage.add(p);
+
+        let mut buf: Vec<u8> = Vec::new();
+        write_html(
+            &mut buf,
+            &storage,
+            &["Project", "Description", "Savings amount"],
+        )
+        .unwrap();
+        let html = str::from_utf8(&buf).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>\n"));
+        assert!(html.contains("<td>Replace &lt;b&gt;butter&lt;/b&gt; &amp; &quot;cream&quot;</td>"));
+        assert!(!html.contains("<b>butter"));
+        assert!(
+            html.contains("<td class=\"number\" data-value=\"141415.942696\">141415.942696</td>")
+        );
+        // sections are ordered by category
+        let bakery = html.find("<h2>Bakery</h2>").unwrap();
+        let dairy = html.find("<h2>Dairy</h2>").unwrap();
+        assert!(bakery < dairy);
+        assert!(html.contains("<td colspan=\"3\">2 projects, 141415.94 EUR savings</td>"));
+        assert!(html.contains("<strong>Total:</strong> 3 projects, 282831.89 EUR savings"));
+    }
+}
@@ -0,0 +1,280 @@
+use project::Project;
+
+// columns searched by full text search with weights of their matches
+pub const SEARCH_COLUMNS: [(&str, f64); 3] = [
+    ("Description", 1.0),
+    ("Category", 2.0),
+    ("Responsible", 2.0),
+];
+
+// marks around highlighted matches
+pub const HIGHLIGHT: (&str, &str) = ("**", "**");
+
+// Search is a full text query of terms over description, category and
+// responsible. Terms are words or "quoted phrases" matching parts of words,
+// case and accents are ignored, so creme matches Crème. Terms must all
+// match unless OR is between them, AND binds tighter than OR, e.g.
+// milk OR cream AND "ice cream"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Search {
+    // alternatives of terms which all have to match,
+    // terms are normalized words separated by single spaces
+    any: Vec<Vec<String>>,
+}
+
+impl Search {
+    pub fn parse(query: &str) -> Result<Search, String> {
+        let mut any = vec![];
+        let mut all: Vec<String> = vec![];
+        // whether previous token was term, so AND or OR may follow
+        let mut after_term = false;
+        let mut rest = query.trim_start();
+        while !rest.is_empty() {
+            let (token, quoted, next) = match rest.strip_prefix('"') {
+                Some(phrase) => match phrase.find('"') {
+                    Some(end) => (&phrase[..end], true, &phrase[end + 1..]),
+                    None => return Err(format!("unclosed \" in search {}", query)),
+                },
+                None => {
+                    let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                    (&rest[..end], false, &rest[end..])
+                }
+            };
+            rest = next.trim_start();
+            match token {
+                "OR" | "AND" if !quoted && !after_term => {
+                    return Err(format!(
+                        "{} without term before it in search {}",
+                        token, query
+                    ))
+                }
+                "OR" if !quoted => {
+                    any.push(std::mem::take(&mut all));
+                    after_term = false;
+                }
+                "AND" if !quoted => after_term = false,
+                _ => {
+                    // punctuation alone is not a term, e.g. & in milk & cream
+                    let term = normalize(token).text;
+                    if !term.is_empty() {
+                        all.push(term);
+                        after_term = true;
+                    }
+                }
+            }
+        }
+        if !after_term {
+            if all.is_empty() && any.is_empty() {
+                return Err("empty search".to_string());
+            }
+            return Err(format!("search {} ends without term", query));
+        }
+        any.push(all);
+        Ok(Search { any })
+    }
+
+    // matches reports whether project matches query
+    pub fn matches(&self, p: &Project) -> bool {
+        let texts = texts(p);
+        self.any
+            .iter()
+            .any(|all| all.iter().all(|t| texts.iter().any(|n| n.text.contains(t))))
+    }
+
+    // score returns relevance of project as weighted number of matches
+    // of terms, 0 if project does not match
+    pub fn score(&self, p: &Project) -> f64 {
+        if !self.matches(p) {
+            return 0.0;
+        }
+        let texts = texts(p);
+        let mut score = 0.0;
+        for term in self.terms() {
+            for (n, (_, weight)) in texts.iter().zip(SEARCH_COLUMNS.iter()) {
+                score += n.text.matches(term.as_str()).count() as f64 * weight;
+            }
+        }
+        score
+    }
+
+    // highlight returns value of searched column with matches of terms
+    // marked by HIGHLIGHT, other columns are returned as is
+    pub fn highlight(&self, column: &str, value: &str) -> String {
+        if !SEARCH_COLUMNS.iter().any(|(c, _)| *c == column) {
+            return value.to_string();
+        }
+        let n = normalize(value);
+        // ranges of matched chars of value
+        let mut ranges: Vec<(usize, usize)> = vec![];
+        for term in self.terms() {
+            for (start, m) in n.text.match_indices(term.as_str()) {
+                ranges.push((n.origin[start], n.origin[start + m.len() - 1] + 1));
+            }
+        }
+        ranges.sort();
+        let mut merged: Vec<(usize, usize)> = vec![];
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+
+        let mut s = String::new();
+        let mut ranges = merged.iter().peekable();
+        for (i, c) in value.chars().enumerate() {
+            if ranges.peek().is_some_and(|r| r.0 == i) {
+                s.push_str(HIGHLIGHT.0);
+            }
+            s.push(c);
+            if ranges.peek().is_some_and(|r| r.1 == i + 1) {
+                s.push_str(HIGHLIGHT.1);
+                ranges.next();
+            }
+        }
+        s
+    }
+
+    fn terms(&self) -> impl Iterator<Item = &String> {
+        self.any.iter().flatten()
+    }
+}
+
+// Normalized is lowercase text without accents and with words
+// separated by single spaces, origin is position of char of text
+// each byte of normalized text comes from
+struct Normalized {
+    text: String,
+    origin: Vec<usize>,
+}
+
+// normalize returns lowercase text without accents,
+// other characters than letters and digits separate words
+fn normalize(s: &str) -> Normalized {
+    let mut n = Normalized {
+        text: String::with_capacity(s.len()),
+        origin: Vec::with_capacity(s.len()),
+    };
+    let mut space = false;
+    for (i, c) in s.chars().enumerate() {
+        if !c.is_alphanumeric() {
+            if !is_accent(c) {
+                space = !n.text.is_empty();
+            }
+            continue;
+        }
+        if space {
+            n.text.push(' ');
+            n.origin.push(i);
+            space = false;
+        }
+        for c in c.to_lowercase().filter(|c| !is_accent(*c)) {
+            let folded = fold(c);
+            for f in folded.chars() {
+                n.text.push(f);
+                n.origin.extend(std::iter::repeat_n(i, f.len_utf8()));
+            }
+        }
+    }
+    n
+}
+
+// is_accent reports whether c is combining accent, accents are dropped
+fn is_accent(c: char) -> bool {
+    ('\u{300}'..='\u{36f}').contains(&c)
+}
+
+// texts returns normalized values of searched columns of project
+fn texts(p: &Project) -> Vec<Normalized> {
+    SEARCH_COLUMNS
+        .iter()
+        .map(|(c, _)| normalize(&p.get(c).unwrap_or_default()))
+        .collect()
+}
+
+// fold returns lowercase latin letter without accent as plain letters
+fn fold(c: char) -> String {
+    let plain = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        c => return c.to_string(),
+    };
+    plain.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::*;
+
+    #[test]
+    fn test_search() {
+        let p1 = get_project(1);
+        let p2 = get_project(2);
+        let search = |q: &str| Search::parse(q).unwrap();
+
+        assert!(search("MILK").matches(&p1));
+        assert!(search("creme fraiche").matches(&p2));
+        assert!(!search("creme fraiche").matches(&p1));
+        assert!(search("\"Crème   Fraîche\"").matches(&p2));
+        assert!(search("ice-cream").matches(&p2));
+        assert!(!search("\"fraiche creme\"").matches(&p2));
+        assert!(search("kryptonite OR lactobacillus").matches(&p1));
+        assert!(!search("kryptonite OR lactobacillus AND evaporated").matches(&p1));
+        assert!(search("kryptonite OR lactobacillus AND daisy").matches(&p1));
+        assert!(search("dairy AND daisy").matches(&p1));
+        assert!(search("creme").matches(&Project {
+            description: "Cre\u{301}me".to_string(),
+            ..get_project(1)
+        }));
+
+        assert!(Search::parse("").is_err());
+        assert!(Search::parse("OR milk").is_err());
+        assert!(Search::parse("milk AND").is_err());
+        assert!(Search::parse("\"milk").is_err());
+        assert!(Search::parse("& -").is_err());
+        assert!(Search::parse("milk AND &").is_err());
+        assert_eq!(search("milk & cream"), search("milk cream"));
+        assert_eq!(search("milk OR \"!\" cream"), search("milk OR cream"));
+
+        // milk in description and in responsible of double weight
+        assert_eq!(search("milk").score(&p2), 3.0);
+        assert_eq!(search("milk OR daisy").score(&p2), 5.0);
+        assert_eq!(search("kryptonite").score(&p2), 0.0);
+
+        assert_eq!(
+            search("creme OR milk").highlight("Description", &p2.description),
+            "Substitute **Crème** fraîche with evaporated **milk** in ice-cream products"
+        );
+        assert_eq!(
+            search("\"ice cream\" cream").highlight("Description", "Ice-Cream"),
+            "**Ice-Cream**"
+        );
+        assert_eq!(
+            search("strasse").highlight("Category", "Straße"),
+            "**Straße**"
+        );
+        assert_eq!(search("milk").highlight("Project", "milk"), "milk");
+    }
+}
@@ -0,0 +1,243 @@
+use chrono::{Datelike, Duration, NaiveDate};
+use std::collections::{BTreeMap, BTreeSet};
+use storage::Storage;
+use table::Table;
+
+// Period is length of time series bucket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl Period {
+    pub fn parse(s: &str) -> Result<Period, String> {
+        match s {
+            "day" => Ok(Period::Day),
+            "week" => Ok(Period::Week),
+            "month" => Ok(Period::Month),
+            "quarter" => Ok(Period::Quarter),
+            "year" => Ok(Period::Year),
+            _ => Err(format!(
+                "unknown period {}, expected day, week, month, quarter or year",
+                s
+            )),
+        }
+    }
+
+    // start returns first day of period containing date,
+    // weeks start on Monday
+    pub fn start(self, d: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => d,
+            Period::Week => d - Duration::days(d.weekday().num_days_from_monday() as i64),
+            Period::Month => NaiveDate::from_ymd_opt(d.year(), d.month(), 1).unwrap(),
+            Period::Quarter => {
+                NaiveDate::from_ymd_opt(d.year(), (d.month() - 1) / 3 * 3 + 1, 1).unwrap()
+            }
+            Period::Year => NaiveDate::from_ymd_opt(d.year(), 1, 1).unwrap(),
+        }
+    }
+
+    // next returns start of period following the one starting on start
+    pub fn next(self, start: NaiveDate) -> NaiveDate {
+        let add_months = |n: u32| {
+            let m = start.month0() + n;
+            NaiveDate::from_ymd_opt(start.year() + (m / 12) as i32, m % 12 + 1, 1).unwrap()
+        };
+        match self {
+            Period::Day => start + Duration::days(1),
+            Period::Week => start + Duration::days(7),
+            Period::Month => add_months(1),
+            Period::Quarter => add_months(3),
+            Period::Year => NaiveDate::from_ymd_opt(start.year() + 1, 1, 1).unwrap(),
+        }
+    }
+
+    // label returns name of period starting on start,
+    // e.g. 2013-01-31, 2013-W05, 2013-01, 2013-Q1 or 2013
+    pub fn label(self, start: NaiveDate) -> String {
+        match self {
+            Period::Day => start.format("%Y-%m-%d").to_string(),
+            Period::Week => {
+                let w = start.iso_week();
+                format!("{}-W{:02}", w.year(), w.week())
+            }
+            Period::Month => start.format("%Y-%m").to_string(),
+            Period::Quarter => format!("{}-Q{}", start.year(), start.month0() / 3 + 1),
+            Period::Year => start.year().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Bucket {
+    projects: usize,
+    // savings amount by currency
+    savings: BTreeMap<String, f64>,
+}
+
+// series buckets projects by start date into periods, optionally split
+// by values of column, from the first to the last start date.
+// Periods without projects have zero counts, every row has running
+// totals since the first period of its group. Savings are summed
+// per currency, one column per currency.
+pub fn series(storage: &Storage, period: Period, by: Option<&str>) -> Result<Table, String> {
+    if let Some(c) = by {
+        if storage.column_type(c).is_none() {
+            return Err(format!("unknown column {}", c));
+        }
+    }
+
+    let mut buckets: BTreeMap<(String, NaiveDate), Bucket> = BTreeMap::new();
+    let mut groups = BTreeSet::new();
+    let mut currencies = BTreeSet::new();
+    for p in storage.iter() {
+        let group = by.map(|c| p.get(c).unwrap_or_default()).unwrap_or_default();
+        groups.insert(group.clone());
+        let bucket = buckets
+            .entry((group, period.start(p.start_date.date())))
+            .or_default();
+        bucket.projects += 1;
+        if let (Some(amount), Some(currency)) = (&p.savings_amount, &p.currency) {
+            currencies.insert(currency.to_string());
+            *bucket.savings.entry(currency.to_string()).or_default() += amount.value();
+        }
+    }
+
+    let mut columns = vec!["Period".to_string()];
+    columns.extend(by.map(|c| c.to_string()));
+    columns.push("Projects".to_string());
+    columns.extend(currencies.iter().map(|c| format!("Savings {}", c)));
+    columns.push("Cumulative projects".to_string());
+    columns.extend(
+        currencies
+            .iter()
+            .map(|c| format!("Cumulative savings {}", c)),
+    );
+    let mut table = Table::new(columns);
+
+    let first = buckets.keys().map(|k| k.1).min();
+    let last = buckets.keys().map(|k| k.1).max();
+    let (first, last) = match (first, last) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Ok(table),
+    };
+
+    let empty = Bucket::default();
+    for group in groups.iter() {
+        let mut total = Bucket::default();
+        let mut start = first;
+        while start <= last {
+            let bucket = buckets.get(&(group.clone(), start)).unwrap_or(&empty);
+            total.projects += bucket.projects;
+            for (c, v) in bucket.savings.iter() {
+                *total.savings.entry(c.clone()).or_default() += v;
+            }
+
+            let mut row = vec![period.label(start)];
+            if by.is_some() {
+                row.push(group.clone());
+            }
+            row.push(bucket.projects.to_string());
+            row.extend(currencies.iter().map(|c| amount(&bucket.savings, c)));
+            row.push(total.projects.to_string());
+            row.extend(currencies.iter().map(|c| amount(&total.savings, c)));
+            table.rows.push(row);
+
+            start = period.next(start);
+        }
+    }
+    Ok(table)
+}
+
+fn amount(savings: &BTreeMap<String, f64>, currency: &str) -> String {
+    format!("{:.2}", savings.get(currency).cloned().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::*;
+
+    #[test]
+    fn test_period() {
+        let d = NaiveDate::from_ymd_opt(2013, 11, 14).unwrap();
+        let cases = vec![
+            (Period::Day, "2013-11-14", "2013-11-15"),
+            (Period::Week, "2013-W46", "2013-11-18"),
+            (Period::Month, "2013-11", "2013-12-01"),
+            (Period::Quarter, "2013-Q4", "2014-01-01"),
+            (Period::Year, "2013", "2014-01-01"),
+        ];
+        for (period, label, next) in cases {
+            let start = period.start(d);
+            assert_eq!(period.label(start), label);
+            assert_eq!(period.next(start).to_string(), next);
+        }
+        assert!(Period::parse("decade").is_err());
+    }
+
+    #[test]
+    fn test_series() {
+        let mut storage = Storage::new(vec![]);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+        let mut p = get_record(2);
+        p[0] = "3";
+        p[2] = "2013-08-01 00:00:00.000";
+        p[3] = "Office supplies";
+        p[5] = "100";
+        storage.insert(p).unwrap();
+
+        let table = series(&storage, Period::Year, None).unwrap();
+        assert_eq!(
+            table.columns,
+            vec![
+                "Period",
+                "Projects",
+                "Savings EUR",
+                "Cumulative projects",
+                "Cumulative savings EUR"
+            ]
+        );
+        assert_eq!(
+            table.rows,
+            vec![
+                vec!["2013", "2", "141515.94", "2", "141515.94"],
+                vec!["2014", "1", "0.00", "3", "141515.94"],
+            ]
+        );
+
+        let table = series(&storage, Period::Quarter, Some("Category")).unwrap();
+        let rows: Vec<(&str, &str, &str, &str)> = table
+            .rows
+            .iter()
+            .map(|r| (r[0].as_str(), r[1].as_str(), r[2].as_str(), r[4].as_str()))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("2013-Q1", "Dairy", "1", "1"),
+                ("2013-Q2", "Dairy", "0", "1"),
+                ("2013-Q3", "Dairy", "0", "1"),
+                ("2013-Q4", "Dairy", "0", "1"),
+                ("2014-Q1", "Dairy", "1", "2"),
+                ("2013-Q1", "Office supplies", "0", "0"),
+                ("2013-Q2", "Office supplies", "0", "0"),
+                ("2013-Q3", "Office supplies", "1", "1"),
+                ("2013-Q4", "Office supplies", "0", "1"),
+                ("2014-Q1", "Office supplies", "0", "1"),
+            ]
+        );
+
+        assert!(series(&storage, Period::Year, Some("Unknown")).is_err());
+        assert!(series(&Storage::new(vec![]), Period::Year, None)
+            .unwrap()
+            .rows
+            .is_empty());
+    }
+}
@@ -0,0 +1,449 @@
+use api::{Cli, Format};
+use project::Project;
+use stats::Stats;
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+use storage::{IndexKind, Page, Storage};
+use table::Table;
+
+// interval of polling listener for connections
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+// longest wait for client to send request or take response,
+// so one idle client does not block others
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    // decoded query parameters in order
+    pub query: Vec<(String, String)>,
+}
+
+impl Request {
+    // parse parses request line, e.g. `GET /projects?project=3 HTTP/1.1`
+    pub fn parse(line: &str) -> Result<Request, String> {
+        let mut parts = line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next()) {
+            (Some(m), Some(t)) => (m, t),
+            _ => return Err(format!("invalid request line {}", line)),
+        };
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let query = query
+            .split('&')
+            .filter(|q| !q.is_empty())
+            .map(|q| {
+                let (k, v) = q.split_once('=').unwrap_or((q, ""));
+                Ok((decode(k)?, decode(v)?))
+            })
+            .collect::<Result<Vec<(String, String)>, String>>()?;
+        Ok(Request {
+            method: method.to_string(),
+            path: decode(path)?,
+            query,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    fn error(status: u16, message: &str) -> Response {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{}\n", message),
+        }
+    }
+
+    fn table(table: &Table, format: Format) -> Response {
+        let mut buf: Vec<u8> = Vec::new();
+        let (content_type, result) = match format {
+            Format::Json => ("application/json", table.write_json(&mut buf)),
+            Format::Tsv => (
+                "text/tab-separated-values; charset=utf-8",
+                table.write_tsv(&mut buf),
+            ),
+            Format::Table => ("text/plain; charset=utf-8", table.write_text(&mut buf)),
+            Format::Markdown => (
+                "text/markdown; charset=utf-8",
+                table.write_markdown(&mut buf),
+            ),
+            Format::Sqlite | Format::Parquet | Format::Xlsx | Format::Html => {
+                return Response::error(400, &format!("{} format is not supported", format))
+            }
+        };
+        match result {
+            Ok(()) => Response {
+                status: 200,
+                content_type,
+                body: String::from_utf8_lossy(&buf).into_owned(),
+            },
+            Err(err) => Response::error(500, &err.to_string()),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        }
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.reason(),
+            self.content_type,
+            self.body.len()
+        )?;
+        writer.write_all(self.body.as_bytes())?;
+        writer.flush()
+    }
+}
+
+// handle answers request with projects of storage:
+//   GET /projects            projects, query parameters are long options
+//                            project, where, search, sort-by, sort-by-start-date,
+//                            columns, format and limit, offset or page
+//                            with limit for pagination
+//   GET /projects/<id>       projects with id
+//   GET /stats               profile of projects
+//   GET /groups?by=<column>  number of projects and savings by column values
+// responses are json by default, format=tsv or format=table change it
+pub fn handle(storage: &Storage, req: &Request) -> Response {
+    if req.method != "GET" {
+        return Response::error(405, "only GET requests are supported");
+    }
+    let mut opts = Cli {
+        format: Format::Json,
+        ..Cli::default()
+    };
+    for (k, v) in req.query.iter() {
+        let result = match k.as_str() {
+            "sort-by-start-date" => match v.as_str() {
+                "" | "true" => opts.apply(k, None),
+                "false" => Ok(()),
+                _ => Err(format!("parameter {} expects true or false", k)),
+            },
+            "offset" | "limit" | "page" | "search" | "project" | "where" | "sort-by"
+            | "columns" | "format" | "by" => opts.apply(k, Some(v)),
+            _ => Err(format!("unknown parameter {}", k)),
+        };
+        if let Err(err) = result {
+            return Response::error(400, &err);
+        }
+    }
+
+    let path: Vec<&str> = req.path.split('/').filter(|s| !s.is_empty()).collect();
+    let result = match path.as_slice() {
+        ["projects"] => opts
+            .pagination()
+            .and_then(|page| list(storage, &opts, page)),
+        ["projects", id] => {
+            let projects = storage.find_by_id(id);
+            if projects.is_empty() {
+                return Response::error(404, &format!("project {} not found", id));
+            }
+            columns(storage, &opts).map(|columns| Table::from_projects(projects, &columns))
+        }
+        ["stats"] => {
+            let filter = opts.filter();
+            let filtered = storage
+                .split_by(|p| filter(p))
+                .remove(&true)
+                .unwrap_or_else(|| Storage::new(vec![]));
+            Ok(Stats::new(&filtered).table())
+        }
+        ["groups"] => groups(storage, &opts),
+        _ => return Response::error(404, &format!("unknown path {}", req.path)),
+    };
+    match result {
+        Ok(table) => Response::table(&table, opts.format),
+        Err(err) => Response::error(400, &err),
+    }
+}
+
+fn list(storage: &Storage, opts: &Cli, page: Page) -> Result<Table, String> {
+    let columns = columns(storage, opts)?;
+    let filter = opts.filter();
+    let mut projects: Vec<&Project> = storage.iter().filter(|p| filter(p)).collect();
+    if opts.sort_by_start_date {
+        projects.sort_by_key(|p| p.start_date);
+    }
+    if let Some(column) = &opts.sort_by {
+        if storage.column_type(column).is_none() {
+            return Err(format!("unknown column {}", column));
+        }
+        projects.sort_by(|a, b| a.compare(b, column));
+    }
+    Ok(Table::from_projects(
+        page.slice(&projects).iter().copied(),
+        &columns,
+    ))
+}
+
+// groups counts projects and sums savings per currency by values of column
+fn groups(storage: &Storage, opts: &Cli) -> Result<Table, String> {
+    let by = match &opts.by {
+        Some(by) => by,
+        None => return Err("groups require by parameter".to_string()),
+    };
+    let filter = opts.filter();
+    let mut currencies = BTreeSet::new();
+    let groups = storage.group_by(by)?;
+    for p in groups.values().flatten().filter(|p| filter(p)) {
+        if let Some(c) = &p.currency {
+            currencies.insert(c.to_string());
+        }
+    }
+
+    let mut columns = vec![by.clone(), "Projects".to_string()];
+    columns.extend(currencies.iter().map(|c| format!("Savings {}", c)));
+    let mut table = Table::new(columns);
+    for (value, projects) in groups.iter() {
+        let projects: Vec<&&Project> = projects.iter().filter(|p| filter(p)).collect();
+        if projects.is_empty() {
+            continue;
+        }
+        let mut row = vec![value.clone(), projects.len().to_string()];
+        for currency in currencies.iter() {
+            let total: f64 = projects
+                .iter()
+                .filter(|p| {
+                    p.currency
+                        .as_ref()
+                        .is_some_and(|c| &c.to_string() == currency)
+                })
+                .filter_map(|p| p.savings_amount.as_ref().map(|a| a.value()))
+                .sum();
+            row.push(format!("{:.2}", total));
+        }
+        table.rows.push(row);
+    }
+    Ok(table)
+}
+
+fn columns<'a>(storage: &'a Storage, opts: &'a Cli) -> Result<Vec<&'a str>, String> {
+    if opts.columns.is_empty() {
+        return Ok(storage.get_columns());
+    }
+    let columns: Vec<&str> = opts.columns.iter().map(|c| c.as_ref()).collect();
+    match columns.iter().find(|c| storage.column_type(c).is_none()) {
+        Some(c) => Err(format!("unknown column {}", c)),
+        None => Ok(columns),
+    }
+}
+
+// decode decodes percent-encoded url component, + is a space
+fn decode(s: &str) -> Result<String, String> {
+    let mut bytes = vec![];
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        match b {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex: Vec<u8> = iter.by_ref().take(2).collect();
+                let hex = String::from_utf8_lossy(&hex).into_owned();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(b) if hex.len() == 2 => bytes.push(b),
+                    _ => return Err(format!("invalid percent-encoding in {}", s)),
+                }
+            }
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| format!("invalid utf-8 in {}", s))
+}
+
+// serve_connection reads request from stream and writes response,
+// request headers and body are ignored
+pub fn serve_connection(stream: TcpStream, storage: &Storage) -> io::Result<()> {
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    // skip headers
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let response = match Request::parse(line.trim_end()) {
+        Ok(req) => handle(storage, &req),
+        Err(err) => Response::error(400, &err),
+    };
+    response.write(stream)
+}
+
+// serve answers requests one by one, errors of connections are reported.
+// tick is called between requests and while waiting for them, so storage
+// can be reloaded without restarting server, projects are indexed by id
+pub fn serve<F>(listener: &TcpListener, storage: &mut Storage, mut tick: F) -> io::Result<()>
+where
+    F: FnMut(&mut Storage),
+{
+    listener.set_nonblocking(true)?;
+    loop {
+        tick(storage);
+        // reloaded storage is indexed again
+        storage.create_index(IndexKind::Id);
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let served = stream
+                    .set_nonblocking(false)
+                    .and_then(|_| serve_connection(stream, storage));
+                if let Err(err) = served {
+                    eprintln!("connection error: {}", err);
+                }
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_INTERVAL)
+            }
+            Err(err) => eprintln!("connection error: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::thread;
+    use test_util::*;
+
+    fn storage() -> Storage {
+        let mut p = get_record(2);
+        p[0] = "3";
+        p[3] = "Office supplies";
+        get_storage(vec![get_record(1), get_record(2), p])
+    }
+
+    fn get(storage: &Storage, target: &str) -> Response {
+        handle(
+            storage,
+            &Request::parse(&format!("GET {} HTTP/1.1", target)).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_handle() {
+        let mut storage = storage();
+
+        let r = get(
+            &storage,
+            "/projects?where=Category%3DDairy&sort-by=Project&columns=Project,Currency&format=tsv",
+        );
+        assert_eq!(r.status, 200);
+        assert_eq!(r.body, "Project\tCurrency\n1\t\n2\tEUR\n");
+
+        let r = get(
+            &storage,
+            "/projects?sort-by=Project&offset=1&limit=1&columns=Project",
+        );
+        assert_eq!(r.content_type, "application/json");
+        assert_eq!(r.body, "[\n{\"Project\":\"2\"}\n]\n");
+        let r = get(
+            &storage,
+            "/projects?sort-by=Project&page=2&limit=2&columns=Project&format=tsv",
+        );
+        assert_eq!(r.body, "Project\n3\n");
+        assert_eq!(get(&storage, "/projects?page=2").status, 400);
+        let r = get(
+            &storage,
+            "/projects?search=%22creme+fraiche%22+office&columns=Project&format=tsv",
+        );
+        assert_eq!(r.body, "Project\n3\n");
+
+        let r = get(&storage, "/projects/3?columns=Project,Category");
+        assert_eq!(
+            r.body,
+            "[\n{\"Project\":\"3\",\"Category\":\"Office supplies\"}\n]\n"
+        );
+        assert_eq!(get(&storage, "/projects/4").status, 404);
+        storage.create_index(IndexKind::Id);
+        let r = get(&storage, "/projects/3?columns=Project&format=tsv");
+        assert_eq!(r.body, "Project\n3\n");
+
+        let r = get(
+            &storage,
+            "/projects?sort-by-start-date=true&columns=Project&format=tsv",
+        );
+        assert_eq!(r.body, "Project\n2\n3\n1\n");
+        let r = get(
+            &storage,
+            "/projects?sort-by-start-date=false&columns=Project&format=tsv",
+        );
+        assert_eq!(r.body, "Project\n1\n2\n3\n");
+        assert_eq!(
+            get(&storage, "/projects?sort-by-start-date=yes").status,
+            400
+        );
+
+        let r = get(&storage, "/groups?by=Category&format=tsv");
+        assert_eq!(
+            r.body,
+            "Category\tProjects\tSavings EUR\nDairy\t2\t141415.94\nOffice supplies\t1\t141415.94\n"
+        );
+
+        let r = get(&storage, "/stats?project=3&format=tsv");
+        assert!(r.body.contains("Projects\t\t1\n"));
+
+        assert_eq!(get(&storage, "/projects?limit=x").status, 400);
+        assert_eq!(get(&storage, "/projects?unknown=1").status, 400);
+        assert_eq!(get(&storage, "/projects?columns=Unknown").status, 400);
+        assert_eq!(get(&storage, "/unknown").status, 404);
+        assert_eq!(
+            handle(
+                &storage,
+                &Request::parse("POST /projects HTTP/1.1").unwrap()
+            )
+            .status,
+            405
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("Savings+amount%3D1").unwrap(), "Savings amount=1");
+        assert_eq!(decode("Cr%C3%A8me").unwrap(), "Crème");
+        assert!(decode("%4").is_err());
+        assert!(decode("%zz").is_err());
+    }
+
+    #[test]
+    fn test_serve_connection() {
+        let storage = storage();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .write_all(b"GET /projects/2?columns=Project&format=tsv HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        serve_connection(stream, &storage).unwrap();
+        let response = client.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\nProject\n2\n"));
+    }
+}
@@ -0,0 +1,311 @@
+use chrono::NaiveDateTime;
+use project::{Project, Type, Value, COLUMN_NAMES, DATE_FORMAT, NULL_STR};
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{params_from_iter, Error};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use storage::Storage;
+
+pub use rusqlite::Connection;
+
+pub const TABLE: &str = "projects";
+
+// first bytes of every sqlite database file
+const HEADER: &[u8] = b"SQLite format 3\0";
+
+// declared types of input columns, dates are ISO text, savings amount
+// is exact decimal text and enums are checked by constraints
+const SCHEMA: [(&str, &str); 8] = [
+    ("Project", "TEXT NOT NULL"),
+    ("Description", "TEXT NOT NULL"),
+    ("Start date", "DATETIME NOT NULL"),
+    ("Category", "TEXT NOT NULL"),
+    ("Responsible", "TEXT NOT NULL"),
+    ("Savings amount", "TEXT"),
+    ("Currency", "TEXT CHECK (\"Currency\" IN ('EUR'))"),
+    (
+        "Complexity",
+        "TEXT NOT NULL CHECK (\"Complexity\" IN ('Simple', 'Moderate', 'Hazardous'))",
+    ),
+];
+
+// columns indexed in exported table
+const INDEXED: [&str; 3] = ["Project", "Category", "Start date"];
+
+// is_database reports whether file at path is sqlite database
+pub fn is_database(path: &Path) -> bool {
+    let mut buf = [0; 16];
+    match File::open(path) {
+        Ok(mut file) => file.read_exact(&mut buf).is_ok() && buf == HEADER,
+        Err(_) => false,
+    }
+}
+
+// export writes columns of projects to table created with typed columns
+// and indexes, existing table is replaced only if replace is set,
+// table is written in one transaction so it is never left half-written
+pub fn export(
+    conn: &mut Connection,
+    storage: &Storage,
+    columns: &[&str],
+    table: &str,
+    replace: bool,
+) -> Result<(), String> {
+    let tx = conn.transaction().map_err(error)?;
+    if table_exists(&tx, table)? {
+        if !replace {
+            return Err(format!("table {} already exists", table));
+        }
+        tx.execute_batch(&format!("DROP TABLE {}", quote(table)))
+            .map_err(error)?;
+    }
+
+    let mut definitions = vec![];
+    for c in columns.iter() {
+        let declared = match SCHEMA.iter().find(|(name, _)| name == c) {
+            Some((_, declared)) => declared,
+            None => match storage.column_type(c) {
+                Some(t) => declared_type(t),
+                None => return Err(format!("unknown column {}", c)),
+            },
+        };
+        definitions.push(format!("{} {}", quote(c), declared));
+    }
+    tx.execute_batch(&format!(
+        "CREATE TABLE {} ({})",
+        quote(table),
+        definitions.join(", ")
+    ))
+    .map_err(error)?;
+    for c in INDEXED.iter().filter(|c| columns.contains(c)) {
+        tx.execute_batch(&format!(
+            "CREATE INDEX {} ON {} ({})",
+            quote(&format!("{}_{}", table, c)),
+            quote(table),
+            quote(c)
+        ))
+        .map_err(error)?;
+    }
+
+    {
+        let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{}", i)).collect();
+        let names: Vec<String> = columns.iter().map(|c| quote(c)).collect();
+        let mut stmt = tx
+            .prepare(&format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                quote(table),
+                names.join(", "),
+                placeholders.join(", ")
+            ))
+            .map_err(error)?;
+        for p in storage.iter() {
+            stmt.execute(params_from_iter(columns.iter().map(|c| sql_value(p, c))))
+                .map_err(error)?;
+        }
+    }
+    tx.commit().map_err(error)
+}
+
+// import reads projects of table into storage, table must have all
+// input columns, other columns are added by their declared types
+pub fn import(conn: &Connection, table: &str, storage: &mut Storage) -> Result<(), String> {
+    if !table_exists(conn, table)? {
+        return Err(format!("table {} not found", table));
+    }
+    let mut stmt = conn
+        .prepare("SELECT name, type FROM pragma_table_info(?1)")
+        .map_err(error)?;
+    let declared = stmt
+        .query_map([table], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(error)?
+        .collect::<Result<Vec<(String, String)>, Error>>()
+        .map_err(error)?;
+
+    for c in COLUMN_NAMES.iter() {
+        if !declared.iter().any(|(name, _)| name == c) {
+            return Err(format!("table {} misses column {}", table, c));
+        }
+    }
+    let mut extra = vec![];
+    for (name, t) in declared.iter() {
+        if COLUMN_NAMES.contains(&name.as_str()) {
+            continue;
+        }
+        let t = column_type(t);
+        storage.add_column(name, t)?;
+        extra.push((name.as_str(), t));
+    }
+
+    let names: Vec<String> = declared.iter().map(|(name, _)| quote(name)).collect();
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM {} ORDER BY rowid",
+            names.join(", "),
+            quote(table)
+        ))
+        .map_err(error)?;
+    let mut rows = stmt.query([]).map_err(error)?;
+    let mut n = 0;
+    while let Some(row) = rows.next().map_err(error)? {
+        n += 1;
+        let value = |column: &str| -> Result<ValueRef, String> {
+            let i = declared
+                .iter()
+                .position(|(name, _)| name == column)
+                .unwrap();
+            row.get_ref(i).map_err(error)
+        };
+
+        let mut record = vec![];
+        for c in COLUMN_NAMES.iter() {
+            record.push(source(value(c)?).map_err(|err| format!("row {}: {}", n, err))?);
+        }
+        let mut p = Project::new(record.iter().map(|v| v.as_str()).collect())
+            .map_err(|err| format!("row {}: {}", n, err))?;
+        for (c, t) in extra.iter() {
+            let v = typed_value(value(c)?, *t).map_err(|err| format!("row {}: {}", n, err))?;
+            p.extra.insert(c.to_string(), v);
+        }
+        storage.add(p);
+    }
+    Ok(())
+}
+
+fn table_exists(conn: &Connection, table: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [table],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|n| n > 0)
+    .map_err(error)
+}
+
+fn declared_type(t: Type) -> &'static str {
+    match t {
+        Type::Str => "TEXT",
+        Type::Number => "REAL",
+        Type::Date => "DATETIME",
+    }
+}
+
+// column_type returns type of added column by declared type
+fn column_type(declared: &str) -> Type {
+    match declared.to_uppercase().as_str() {
+        "REAL" | "INTEGER" | "NUMERIC" => Type::Number,
+        "DATETIME" | "DATE" => Type::Date,
+        _ => Type::Str,
+    }
+}
+
+// sql_value returns value of column as stored in table
+fn sql_value(p: &Project, column: &str) -> SqlValue {
+    match p.value_of(column) {
+        None | Some(Value::Null) => SqlValue::Null,
+        Some(Value::Str(s)) => SqlValue::Text(s),
+        Some(Value::Date(d)) => SqlValue::Text(d.format(DATE_FORMAT).to_string()),
+        // money keeps all printed decimals
+        Some(Value::Number(_)) if column == "Savings amount" => {
+            SqlValue::Text(p.get(column).unwrap_or_default())
+        }
+        Some(Value::Number(n)) => SqlValue::Real(n),
+    }
+}
+
+// source returns value of input column in input format
+fn source(v: ValueRef) -> Result<String, String> {
+    match v {
+        ValueRef::Null => Ok(NULL_STR.to_string()),
+        ValueRef::Integer(i) => Ok(i.to_string()),
+        ValueRef::Real(r) => Ok(r.to_string()),
+        ValueRef::Text(s) => String::from_utf8(s.to_vec()).map_err(|e| e.to_string()),
+        ValueRef::Blob(_) => Err("blob values are not supported".to_string()),
+    }
+}
+
+fn typed_value(v: ValueRef, t: Type) -> Result<Value, String> {
+    match (v, t) {
+        (ValueRef::Null, _) => Ok(Value::Null),
+        (ValueRef::Integer(i), Type::Number) => Ok(Value::Number(i as f64)),
+        (ValueRef::Real(r), Type::Number) => Ok(Value::Number(r)),
+        (v, Type::Date) => {
+            let s = source(v)?;
+            match NaiveDateTime::parse_from_str(&s, DATE_FORMAT) {
+                Ok(d) => Ok(Value::Date(d)),
+                Err(_) => Err(format!("date parse error {}", s)),
+            }
+        }
+        (v, t) => Value::parse(t, &source(v)?),
+    }
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn error(err: Error) -> String {
+    err.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expr::Derived;
+    use test_util::*;
+
+    fn storage() -> Storage {
+        let mut storage = Storage::new(vec![]);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+        let d = Derived::parse("Savings kEUR = Savings amount / 1000").unwrap();
+        storage.derive(&d, get_project(1).start_date).unwrap();
+        storage
+    }
+
+    #[test]
+    fn test_export_import() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let s = storage();
+        export(&mut conn, &s, &s.get_columns(), TABLE, false).unwrap();
+
+        let amount: String = conn
+            .query_row(
+                "SELECT \"Savings amount\" FROM projects WHERE \"Project\" = '2'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(amount, "141415.942696");
+        let indexes: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM sqlite_master WHERE type = 'index'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(indexes, 3);
+        // enum values are checked
+        assert!(conn
+            .execute("UPDATE projects SET \"Complexity\" = 'Easy'", [])
+            .is_err());
+
+        let mut imported = Storage::new(vec![]);
+        import(&conn, TABLE, &mut imported).unwrap();
+        assert_eq!(imported.get_columns(), s.get_columns());
+        assert_eq!(imported.column_type("Savings kEUR"), Some(Type::Number));
+        let projects: Vec<&Project> = imported.iter().collect();
+        assert_eq!(projects, s.iter().collect::<Vec<&Project>>());
+
+        assert_eq!(
+            export(&mut conn, &s, &s.get_columns(), TABLE, false).unwrap_err(),
+            "table projects already exists"
+        );
+        export(&mut conn, &imported, &["Project", "Category"], TABLE, true).unwrap();
+        assert_eq!(
+            import(&conn, TABLE, &mut Storage::new(vec![])).unwrap_err(),
+            "table projects misses column Description"
+        );
+        assert!(import(&conn, "unknown", &mut Storage::new(vec![])).is_err());
+    }
+}
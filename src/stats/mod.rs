@@ -0,0 +1,290 @@
+use chrono::NaiveDateTime;
+use project::{Type, Value};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+use storage::Storage;
+use table::Table;
+
+// number of most frequent values reported for string columns
+const TOP_VALUES: usize = 3;
+const PERCENTILES: [(&str, f64); 3] = [("p25", 0.25), ("p75", 0.75), ("p90", 0.9)];
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ColumnStats {
+    pub column: String,
+    pub nulls: usize,
+    pub distinct: usize,
+    // most frequent values with counts, only for string columns
+    pub top: Vec<(String, usize)>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct AmountStats {
+    pub count: usize,
+    pub total: f64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub percentiles: Vec<(&'static str, f64)>,
+}
+
+// Stats is a profile of projects in storage
+#[derive(Debug, Default, PartialEq)]
+pub struct Stats {
+    pub projects: usize,
+    // comment and blank lines kept in storage
+    pub skipped: usize,
+    // projects equal to a previous one in all columns
+    pub duplicates: usize,
+    // projects with id of a previous one
+    pub duplicate_ids: usize,
+    pub columns: Vec<ColumnStats>,
+    // savings amount by currency
+    pub savings: BTreeMap<String, AmountStats>,
+    pub first_start_date: Option<NaiveDateTime>,
+    pub last_start_date: Option<NaiveDateTime>,
+}
+
+impl Stats {
+    pub fn new(storage: &Storage) -> Stats {
+        let columns = storage.get_columns();
+        let mut stats = Stats {
+            skipped: storage.annotations().len(),
+            ..Stats::default()
+        };
+
+        let mut rows = HashSet::new();
+        let mut ids = HashSet::new();
+        let mut amounts: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+        for p in storage.iter() {
+            stats.projects += 1;
+            let row: Vec<String> = columns
+                .iter()
+                .map(|c| p.get(c).unwrap_or_default())
+                .collect();
+            if !rows.insert(row) {
+                stats.duplicates += 1;
+            }
+            if !ids.insert(&p.id) {
+                stats.duplicate_ids += 1;
+            }
+            if let (Some(amount), Some(currency)) = (&p.savings_amount, &p.currency) {
+                amounts
+                    .entry(currency.to_string())
+                    .or_default()
+                    .push(amount.value());
+            }
+            if stats.first_start_date.is_none_or(|d| p.start_date < d) {
+                stats.first_start_date = Some(p.start_date);
+            }
+            if stats.last_start_date.is_none_or(|d| p.start_date > d) {
+                stats.last_start_date = Some(p.start_date);
+            }
+        }
+
+        stats.columns = columns.iter().map(|c| column_stats(storage, c)).collect();
+        stats.savings = amounts
+            .into_iter()
+            .map(|(currency, v)| (currency, amount_stats(v)))
+            .collect();
+        stats
+    }
+
+    // table returns statistic, column and value of every statistic
+    pub fn table(&self) -> Table {
+        let mut rows = vec![
+            ("Projects", String::new(), self.projects.to_string()),
+            ("Skipped lines", String::new(), self.skipped.to_string()),
+            ("Duplicates", String::new(), self.duplicates.to_string()),
+            (
+                "Duplicate ids",
+                String::new(),
+                self.duplicate_ids.to_string(),
+            ),
+        ];
+        for c in self.columns.iter() {
+            rows.push(("Nulls", c.column.clone(), c.nulls.to_string()));
+            rows.push(("Distinct", c.column.clone(), c.distinct.to_string()));
+            if !c.top.is_empty() {
+                let top: Vec<String> = c
+                    .top
+                    .iter()
+                    .map(|(v, n)| format!("{} ({})", v, n))
+                    .collect();
+                rows.push(("Top values", c.column.clone(), top.join(", ")));
+            }
+        }
+        for (currency, a) in self.savings.iter() {
+            let column = format!("Savings amount {}", currency);
+            let mut values = vec![
+                ("Count", a.count as f64),
+                ("Total", a.total),
+                ("Min", a.min),
+                ("Max", a.max),
+                ("Mean", a.mean),
+                ("Median", a.median),
+            ];
+            values.extend(a.percentiles.iter().cloned());
+            for (name, v) in values {
+                let v = if name == "Count" {
+                    v.to_string()
+                } else {
+                    format!("{:.2}", v)
+                };
+                rows.push((name, column.clone(), v));
+            }
+        }
+        if let (Some(first), Some(last)) = (self.first_start_date, self.last_start_date) {
+            rows.push(("Min", "Start date".to_string(), first.date().to_string()));
+            rows.push(("Max", "Start date".to_string(), last.date().to_string()));
+        }
+        let mut table = Table::new(vec![
+            "Statistic".to_string(),
+            "Column".to_string(),
+            "Value".to_string(),
+        ]);
+        table.rows = rows
+            .into_iter()
+            .map(|(name, column, value)| vec![name.to_string(), column, value])
+            .collect();
+        table
+    }
+}
+
+fn column_stats(storage: &Storage, column: &str) -> ColumnStats {
+    let mut stats = ColumnStats {
+        column: column.to_string(),
+        ..ColumnStats::default()
+    };
+    let mut counts: BTreeMap<Value, usize> = BTreeMap::new();
+    for p in storage.iter() {
+        match p.value_of(column) {
+            Some(Value::Null) | None => stats.nulls += 1,
+            Some(v) => *counts.entry(v).or_default() += 1,
+        }
+    }
+    stats.distinct = counts.len();
+
+    if storage.column_type(column) == Some(Type::Str) {
+        let mut top: Vec<(String, usize)> = counts
+            .into_iter()
+            .map(|(v, n)| (v.to_string(), n))
+            .collect();
+        // most frequent first, ties in order of values
+        top.sort_by_key(|t| Reverse(t.1));
+        top.truncate(TOP_VALUES);
+        stats.top = top;
+    }
+    stats
+}
+
+fn amount_stats(mut values: Vec<f64>) -> AmountStats {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let total: f64 = values.iter().sum();
+    AmountStats {
+        count: values.len(),
+        total,
+        min: values[0],
+        max: values[values.len() - 1],
+        mean: total / values.len() as f64,
+        median: percentile(&values, 0.5),
+        percentiles: PERCENTILES
+            .iter()
+            .map(|(name, q)| (*name, percentile(&values, *q)))
+            .collect(),
+    }
+}
+
+// percentile returns q-th percentile of sorted values
+// interpolated linearly between closest ranks
+fn percentile(sorted: &[f64], q: f64) -> f64 {
+    let rank = q * (sorted.len() - 1) as f64;
+    let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str;
+    use test_util::*;
+
+    #[test]
+    fn test_stats() {
+        let mut storage = Storage::new(vec![]);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+        let mut p = get_record(2);
+        p[0] = "3";
+        p[5] = "100";
+        storage.insert(p).unwrap();
+        storage.annotate("# comment", Some(1));
+
+        let stats = Stats::new(&storage);
+        assert_eq!(
+            (
+                stats.projects,
+                stats.skipped,
+                stats.duplicates,
+                stats.duplicate_ids
+            ),
+            (4, 1, 1, 1)
+        );
+        assert_eq!(
+            stats.columns[6],
+            ColumnStats {
+                column: "Currency".to_string(),
+                nulls: 1,
+                distinct: 1,
+                top: vec![("EUR".to_string(), 3)],
+            }
+        );
+        assert_eq!(stats.columns[5].top, vec![]);
+        assert_eq!(
+            stats.columns[0].top,
+            vec![
+                ("2".to_string(), 2),
+                ("1".to_string(), 1),
+                ("3".to_string(), 1)
+            ]
+        );
+
+        let mut buf: Vec<u8> = Vec::new();
+        stats.table().write_tsv(&mut buf).unwrap();
+        let out = str::from_utf8(&buf).unwrap();
+        assert!(out.starts_with(
+            "Statistic\tColumn\tValue
+Projects\t\t4
+Skipped lines\t\t1
+Duplicates\t\t1
+Duplicate ids\t\t1
+Nulls\tProject\t0
+"
+        ));
+        assert!(out.ends_with(
+            "Count\tSavings amount EUR\t3
+Total\tSavings amount EUR\t282931.89
+Min\tSavings amount EUR\t100.00
+Max\tSavings amount EUR\t141415.94
+Mean\tSavings amount EUR\t94310.63
+Median\tSavings amount EUR\t141415.94
+p25\tSavings amount EUR\t70757.97
+p75\tSavings amount EUR\t141415.94
+p90\tSavings amount EUR\t141415.94
+Min\tStart date\t2013-01-01
+Max\tStart date\t2014-01-01
+"
+        ));
+    }
+
+    #[test]
+    fn test_percentile() {
+        let values = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(percentile(&values, 0.5), 2.5);
+        assert_eq!(percentile(&values, 0.0), 1.0);
+        assert_eq!(percentile(&values, 1.0), 4.0);
+        assert_eq!(percentile(&[5.0], 0.9), 5.0);
+    }
+}
@@ -0,0 +1,142 @@
+use chrono::NaiveDateTime;
+use project::Project;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+
+// IndexKind lists columns which can have a secondary index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndexKind {
+    Id,
+    Category,
+    Responsible,
+    StartDate,
+    SavingsAmount,
+}
+
+// AmountKey gives total ordering to savings amount so it can be a BTreeMap key
+#[derive(Debug, Clone, Copy)]
+pub struct AmountKey(pub f64);
+
+impl PartialEq for AmountKey {
+    fn eq(&self, other: &AmountKey) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for AmountKey {}
+
+impl PartialOrd for AmountKey {
+    fn partial_cmp(&self, other: &AmountKey) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AmountKey {
+    fn cmp(&self, other: &AmountKey) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+// Indexes keeps positions of projects in storage by column values,
+// only enabled indexes are maintained
+#[derive(Default)]
+pub struct Indexes {
+    pub id: Option<HashMap<String, Vec<usize>>>,
+    pub category: Option<HashMap<String, Vec<usize>>>,
+    pub responsible: Option<HashMap<String, Vec<usize>>>,
+    pub start_date: Option<BTreeMap<NaiveDateTime, Vec<usize>>>,
+    pub savings_amount: Option<BTreeMap<AmountKey, Vec<usize>>>,
+}
+
+impl Indexes {
+    pub fn enable(&mut self, kind: IndexKind) {
+        match kind {
+            IndexKind::Id => {
+                self.id.get_or_insert_with(HashMap::new);
+            }
+            IndexKind::Category => {
+                self.category.get_or_insert_with(HashMap::new);
+            }
+            IndexKind::Responsible => {
+                self.responsible.get_or_insert_with(HashMap::new);
+            }
+            IndexKind::StartDate => {
+                self.start_date.get_or_insert_with(BTreeMap::new);
+            }
+            IndexKind::SavingsAmount => {
+                self.savings_amount.get_or_insert_with(BTreeMap::new);
+            }
+        }
+    }
+
+    pub fn is_enabled(&self, kind: IndexKind) -> bool {
+        match kind {
+            IndexKind::Id => self.id.is_some(),
+            IndexKind::Category => self.category.is_some(),
+            IndexKind::Responsible => self.responsible.is_some(),
+            IndexKind::StartDate => self.start_date.is_some(),
+            IndexKind::SavingsAmount => self.savings_amount.is_some(),
+        }
+    }
+
+    // add puts project at position pos into all enabled indexes
+    pub fn add(&mut self, pos: usize, p: &Project) {
+        if let Some(idx) = self.id.as_mut() {
+            idx.entry(p.id.clone()).or_default().push(pos);
+        }
+        if let Some(idx) = self.category.as_mut() {
+            idx.entry(p.category.clone()).or_default().push(pos);
+        }
+        if let Some(idx) = self.responsible.as_mut() {
+            idx.entry(p.responsible.clone()).or_default().push(pos);
+        }
+        if let Some(idx) = self.start_date.as_mut() {
+            idx.entry(p.start_date).or_default().push(pos);
+        }
+        if let Some(idx) = self.savings_amount.as_mut() {
+            if let Some(ref v) = p.savings_amount {
+                idx.entry(AmountKey(v.value())).or_default().push(pos);
+            }
+        }
+    }
+
+    // clear removes indexed positions but keeps indexes enabled
+    pub fn clear(&mut self) {
+        if let Some(idx) = self.id.as_mut() {
+            idx.clear();
+        }
+        if let Some(idx) = self.category.as_mut() {
+            idx.clear();
+        }
+        if let Some(idx) = self.responsible.as_mut() {
+            idx.clear();
+        }
+        if let Some(idx) = self.start_date.as_mut() {
+            idx.clear();
+        }
+        if let Some(idx) = self.savings_amount.as_mut() {
+            idx.clear();
+        }
+    }
+}
+
+// is_empty_range reports whether range of bounds can not contain any value,
+// BTreeMap::range panics on such ranges
+pub fn is_empty_range<T: Ord>(start: Bound<&T>, end: Bound<&T>) -> bool {
+    match (start, end) {
+        (Bound::Included(s), Bound::Included(e)) => s > e,
+        (Bound::Included(s), Bound::Excluded(e))
+        | (Bound::Excluded(s), Bound::Included(e))
+        | (Bound::Excluded(s), Bound::Excluded(e)) => s >= e,
+        _ => false,
+    }
+}
+
+pub fn amount_bound(b: Bound<&f64>) -> Bound<AmountKey> {
+    match b {
+        Bound::Included(v) => Bound::Included(AmountKey(*v)),
+        Bound::Excluded(v) => Bound::Excluded(AmountKey(*v)),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
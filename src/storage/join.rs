@@ -0,0 +1,150 @@
+use super::Storage;
+use project::{Project, Type, Value};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinKind {
+    // projects without matching lookup row are removed
+    Inner,
+    // projects without matching lookup row get empty values
+    Left,
+}
+
+// Lookup is a table of values by key, used to add columns to projects
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lookup {
+    pub key: String,
+    pub columns: Vec<String>,
+    rows: HashMap<String, Vec<String>>,
+}
+
+impl Lookup {
+    pub fn new(key: &str, columns: Vec<String>) -> Lookup {
+        Lookup {
+            key: key.to_string(),
+            columns,
+            rows: HashMap::new(),
+        }
+    }
+
+    // insert adds values of columns by key, keys must be unique
+    pub fn insert(&mut self, key: &str, values: Vec<String>) -> Result<(), String> {
+        if values.len() != self.columns.len() {
+            return Err("wrong number of columns".to_string());
+        }
+        if self.rows.contains_key(key) {
+            return Err(format!("duplicate lookup key {}", key));
+        }
+        self.rows.insert(key.to_string(), values);
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Vec<String>> {
+        self.rows.get(key)
+    }
+
+    pub fn size(&self) -> usize {
+        self.rows.len()
+    }
+}
+
+impl Storage {
+    // join adds lookup columns to projects matching value of column `on`
+    // with lookup key, returns projects without matching lookup row
+    pub fn join(
+        &mut self,
+        lookup: &Lookup,
+        on: &str,
+        kind: JoinKind,
+    ) -> Result<Vec<Project>, String> {
+        if !self.columns.contains_key(on) {
+            return Err(format!("unknown join column {}", on));
+        }
+        for c in lookup.columns.iter() {
+            if self.columns.contains_key(c) {
+                return Err(format!("column {} already exists", c));
+            }
+        }
+        for c in lookup.columns.iter() {
+            self.add_column(c, Type::Str)?;
+        }
+
+        let mut unmatched = vec![];
+        for p in self.projects.iter_mut() {
+            let values = p.get(on).and_then(|v| lookup.get(&v));
+            if values.is_none() {
+                unmatched.push(p.clone());
+            }
+            for (i, c) in lookup.columns.iter().enumerate() {
+                let v = match values {
+                    Some(v) => Value::Str(v[i].clone()),
+                    None => Value::Null,
+                };
+                p.extra.insert(c.clone(), v);
+            }
+        }
+
+        if kind == JoinKind::Inner && !unmatched.is_empty() {
+            self.delete(|p| p.get(on).and_then(|v| lookup.get(&v)).is_none());
+        }
+        Ok(unmatched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::*;
+
+    fn lookup() -> Lookup {
+        let mut lookup = Lookup::new(
+            "Responsible",
+            vec!["Department".to_string(), "Cost center".to_string()],
+        );
+        lookup
+            .insert("Daisy Milks", vec!["Dairy".to_string(), "100".to_string()])
+            .unwrap();
+        lookup
+    }
+
+    #[test]
+    fn test_join() {
+        let mut other = get_record(2);
+        other[4] = "Clark Kent";
+
+        for kind in [JoinKind::Left, JoinKind::Inner] {
+            let mut storage = Storage::new(vec![]);
+            storage.insert(get_record(1)).unwrap();
+            storage.insert(other.clone()).unwrap();
+
+            let unmatched = storage.join(&lookup(), "Responsible", kind).unwrap();
+            assert_eq!(unmatched.len(), 1);
+            assert_eq!(unmatched[0].id, "2");
+            assert_eq!(
+                storage.get_columns()[8..].to_vec(),
+                vec!["Department", "Cost center"]
+            );
+
+            let p = storage.iter().next().unwrap();
+            assert_eq!(p.get("Department").unwrap(), "Dairy");
+            assert_eq!(p.get("Cost center").unwrap(), "100");
+            if kind == JoinKind::Left {
+                assert_eq!(storage.size(), 2);
+                assert_eq!(
+                    storage.iter().last().unwrap().get("Department").unwrap(),
+                    ""
+                );
+            } else {
+                assert_eq!(storage.size(), 1);
+            }
+            // joined columns can't be added twice
+            assert!(storage.join(&lookup(), "Responsible", kind).is_err());
+        }
+
+        let mut storage = Storage::new(vec![]);
+        assert!(storage.join(&lookup(), "Unknown", JoinKind::Left).is_err());
+        assert!(lookup()
+            .insert("Daisy Milks", vec!["".to_string(), "".to_string()])
+            .is_err());
+    }
+}
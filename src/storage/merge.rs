@@ -0,0 +1,183 @@
+use super::Storage;
+use diff::{changed_fields, FieldChange};
+use std::collections::HashMap;
+use std::fmt;
+
+// ConflictPolicy decides which project is kept when merged projects
+// have the same id but different values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictPolicy {
+    FirstWins,
+    LastWins,
+    // project with later start date is kept, on tie the first one
+    NewestStartDate,
+    Fail,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub id: String,
+    // old values are of the first project, new ones of the merged project
+    pub fields: Vec<FieldChange>,
+    // whether merged project replaced the first one
+    pub replaced: bool,
+    // policy that decided which project is kept
+    pub policy: ConflictPolicy,
+}
+
+impl Conflict {
+    fn describe(&self) -> String {
+        let fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|c| format!("{}: {:?} vs {:?}", c.column, c.old, c.new))
+            .collect();
+        format!("conflict on project {} ({})", self.id, fields.join(", "))
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kept = if self.replaced { "last" } else { "first" };
+        match self.policy {
+            ConflictPolicy::NewestStartDate => {
+                write!(f, "{}, {} kept by newest start date", self.describe(), kept)
+            }
+            _ => write!(f, "{}, {} kept", self.describe(), kept),
+        }
+    }
+}
+
+impl Storage {
+    // merge upserts projects of other storage by project id,
+    // projects with new ids are appended, equal projects are skipped
+    // and different ones are resolved by policy. Returns conflicts,
+    // with Fail policy storage is not changed and error describes
+    // the first conflict
+    pub fn merge(
+        &mut self,
+        other: &Storage,
+        policy: ConflictPolicy,
+    ) -> Result<Vec<Conflict>, String> {
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for (i, p) in self.projects.iter().enumerate() {
+            positions.entry(p.id.clone()).or_insert(i);
+        }
+
+        let mut projects = self.projects.clone();
+        let mut conflicts = vec![];
+        for p in other.iter() {
+            let i = match positions.get(&p.id) {
+                Some(i) => *i,
+                None => {
+                    positions.insert(p.id.clone(), projects.len());
+                    projects.push(p.clone());
+                    continue;
+                }
+            };
+
+            let fields = changed_fields(&projects[i], p);
+            if fields.is_empty() {
+                continue;
+            }
+            let replaced = match policy {
+                ConflictPolicy::FirstWins => false,
+                ConflictPolicy::LastWins => true,
+                ConflictPolicy::NewestStartDate => p.start_date > projects[i].start_date,
+                ConflictPolicy::Fail => {
+                    let c = Conflict {
+                        id: p.id.clone(),
+                        fields,
+                        replaced: false,
+                        policy,
+                    };
+                    return Err(c.describe());
+                }
+            };
+            if replaced {
+                projects[i] = p.clone();
+            }
+            conflicts.push(Conflict {
+                id: p.id.clone(),
+                fields,
+                replaced,
+                policy,
+            });
+        }
+
+        self.projects = projects;
+        self.reindex();
+        Ok(conflicts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::IndexKind;
+    use super::*;
+    use test_util::*;
+
+    #[test]
+    fn test_merge() {
+        let mut newer = get_record(2);
+        newer[2] = "2015-01-01 00:00:00.000";
+        let mut older = get_record(1);
+        older[2] = "2010-01-01 00:00:00.000";
+        older[4] = "Clark Kent";
+        let other = get_storage(vec![get_record(2), newer, older]);
+
+        struct Data(ConflictPolicy, Vec<&'static str>, Vec<bool>);
+        let cases = vec![
+            Data(
+                ConflictPolicy::FirstWins,
+                vec!["2014-01-01", "2013-01-01"],
+                vec![false, false],
+            ),
+            Data(
+                ConflictPolicy::LastWins,
+                vec!["2010-01-01", "2015-01-01"],
+                vec![true, true],
+            ),
+            Data(
+                ConflictPolicy::NewestStartDate,
+                vec!["2014-01-01", "2015-01-01"],
+                vec![true, false],
+            ),
+        ];
+
+        for Data(policy, dates, replaced) in cases {
+            let mut s = get_storage(vec![get_record(1)]);
+            s.create_index(IndexKind::Id);
+            let conflicts = s.merge(&other, policy).unwrap();
+
+            assert_eq!(s.size(), 2);
+            assert_eq!(
+                s.iter()
+                    .map(|p| p.start_date.format("%Y-%m-%d").to_string())
+                    .collect::<Vec<String>>(),
+                dates
+            );
+            // equal project 2 is skipped, newer one conflicts with it
+            assert_eq!(
+                conflicts.iter().map(|c| c.replaced).collect::<Vec<bool>>(),
+                replaced
+            );
+            assert_eq!(conflicts[0].id, "2");
+            assert_eq!(conflicts[1].fields.len(), 2);
+            assert!(conflicts[0].to_string().ends_with(match policy {
+                ConflictPolicy::NewestStartDate => "last kept by newest start date",
+                ConflictPolicy::FirstWins => "first kept",
+                _ => "last kept",
+            }));
+            assert_eq!(s.find_by_id("2").len(), 1);
+        }
+
+        let mut s = get_storage(vec![get_record(1)]);
+        assert!(s.merge(&other, ConflictPolicy::Fail).is_err());
+        assert_eq!(s.size(), 1);
+        assert!(s
+            .merge(&get_storage(vec![get_record(2)]), ConflictPolicy::Fail)
+            .is_ok());
+        assert_eq!(s.size(), 2);
+    }
+}
@@ -1,66 +1,426 @@
-use project::{Project, COLUMN_NAMES};
-use std::collections::HashMap;
+use chrono::NaiveDateTime;
+use expr::Derived;
+use project::{column_type, Project, Type, Value, COLUMN_NAMES};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeBounds;
 use std::slice::Iter;
 
+mod index;
+mod join;
+mod merge;
+mod page;
+mod rank;
+mod relevance;
+
+pub use self::index::IndexKind;
+use self::index::{amount_bound, is_empty_range, AmountKey, Indexes};
+pub use self::join::{JoinKind, Lookup};
+pub use self::merge::{Conflict, ConflictPolicy};
+pub use self::page::Page;
+pub use self::rank::{Ranking, RANK_COLUMN};
+pub use self::relevance::RELEVANCE_COLUMN;
+
+pub type Filter = Box<dyn Fn(&Project) -> bool>;
+
+// Annotation is a comment or blank line of input kept for round-trip,
+// row is number of stored projects preceding the line or None
+// if the line precedes header
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub line: String,
+    pub row: Option<usize>,
+}
+
+// Layout is how lines of input file were written, kept for round-trip,
+// header is header line as read
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    pub crlf: bool,
+    pub final_newline: bool,
+    pub header: Option<String>,
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout {
+            crlf: false,
+            final_newline: true,
+            header: None,
+        }
+    }
+}
+
 pub struct Storage {
     columns: HashMap<String, usize>,
+    // types of columns added after parsing
+    types: HashMap<String, Type>,
     projects: Vec<Project>,
-    filters: Vec<Box<dyn Fn(&Project) -> bool>>,
+    filters: Vec<Filter>,
+    indexes: Indexes,
+    annotations: Vec<Annotation>,
+    layout: Layout,
+    reordered: bool,
 }
 
 impl Storage {
-    pub fn new(filters: Vec<Box<dyn Fn(&Project) -> bool>>) -> Storage {
+    pub fn new(filters: Vec<Filter>) -> Storage {
         let mut storage = Storage {
             columns: HashMap::with_capacity(8),
+            types: HashMap::new(),
             projects: vec![],
             filters,
+            indexes: Indexes::default(),
+            annotations: vec![],
+            layout: Layout::default(),
+            reordered: false,
         };
         storage.set_columns(COLUMN_NAMES.to_vec());
 
         storage
     }
 
+    // set_columns sets columns of input file in order, columns other than
+    // project columns are kept as strings in extra columns of projects
     pub fn set_columns(&mut self, record: Vec<&str>) {
         for (i, v) in record.iter().enumerate() {
             self.columns.insert(v.to_string(), i);
+            if column_type(v).is_none() {
+                self.types.entry(v.to_string()).or_insert(Type::Str);
+            }
         }
     }
 
     pub fn get_columns(&self) -> Vec<&str> {
-        let mut columns = [""; 8];
-        for (k, v) in self.columns.iter() {
-            columns[*v as usize] = k;
+        let mut columns: Vec<(usize, &str)> =
+            self.columns.iter().map(|(k, v)| (*v, k.as_str())).collect();
+        columns.sort();
+
+        columns.into_iter().map(|(_, k)| k).collect()
+    }
+
+    // add_column appends column of type after existing ones,
+    // values are expected in extra columns of projects
+    pub fn add_column(&mut self, column: &str, t: Type) -> Result<(), String> {
+        if self.columns.contains_key(column) {
+            return Err(format!("column {} already exists", column));
         }
+        let n = self.columns.len();
+        self.columns.insert(column.to_string(), n);
+        self.types.insert(column.to_string(), t);
+        Ok(())
+    }
 
-        columns.to_vec()
+    // column_type returns type of existing column
+    pub fn column_type(&self, column: &str) -> Option<Type> {
+        if !self.columns.contains_key(column) {
+            return None;
+        }
+        column_type(column).or_else(|| self.types.get(column).cloned())
     }
 
     pub fn sort_by_start_date(&mut self) {
-        self.projects
-            .sort_by(|a, b| a.start_date.cmp(&b.start_date));
+        self.projects.sort_by_key(|p| p.start_date);
+        self.reordered = true;
+        self.reindex();
+    }
+
+    // derive adds column computed by expression for stored projects,
+    // expression is checked against types of existing columns,
+    // now is the value of `today`
+    pub fn derive(&mut self, d: &Derived, now: NaiveDateTime) -> Result<(), String> {
+        let t = d
+            .expr
+            .check(&|c: &str| self.column_type(c))
+            .map_err(|e| format!("column {}: {}", d.name, e))?;
+        self.add_column(&d.name, t)?;
+        for p in self.projects.iter_mut() {
+            let v = d.expr.eval(p, now);
+            p.extra.insert(d.name.clone(), v);
+        }
+        Ok(())
+    }
+
+    // sort_by_column sorts projects by column in ascending order,
+    // order of equal projects is kept
+    pub fn sort_by_column(&mut self, column: &str) -> Result<(), String> {
+        if !self.columns.contains_key(column) {
+            return Err(format!("unknown column {}", column));
+        }
+        self.projects.sort_by(|a, b| a.compare(b, column));
+        self.reordered = true;
+        self.reindex();
+        Ok(())
+    }
+
+    // group_by groups projects by value of column as printed in output,
+    // groups are ordered by value and projects keep storage order
+    pub fn group_by(&self, column: &str) -> Result<BTreeMap<String, Vec<&Project>>, String> {
+        if !self.columns.contains_key(column) {
+            return Err(format!("unknown column {}", column));
+        }
+        let mut groups: BTreeMap<String, Vec<&Project>> = BTreeMap::new();
+        for p in self.projects.iter() {
+            groups
+                .entry(p.get(column).unwrap_or_default())
+                .or_default()
+                .push(p);
+        }
+        Ok(groups)
+    }
+
+    // split_by splits projects into storages by key, ordered by key,
+    // each storage has the same columns and projects keep storage order
+    pub fn split_by<K: Ord, F: Fn(&Project) -> K>(&self, key: F) -> BTreeMap<K, Storage> {
+        let mut parts: BTreeMap<K, Storage> = BTreeMap::new();
+        for p in self.projects.iter() {
+            let part = parts.entry(key(p)).or_insert_with(|| Storage {
+                columns: self.columns.clone(),
+                types: self.types.clone(),
+                projects: vec![],
+                filters: vec![],
+                indexes: Indexes::default(),
+                annotations: vec![],
+                layout: self.layout.clone(),
+                reordered: false,
+            });
+            part.projects.push(p.clone());
+        }
+        parts
+    }
+
+    // create_index enables secondary index on column and builds it
+    // from already stored projects, index is maintained on insert
+    pub fn create_index(&mut self, kind: IndexKind) {
+        if self.indexes.is_enabled(kind) {
+            return;
+        }
+        self.indexes.enable(kind);
+        self.reindex();
+    }
+
+    fn reindex(&mut self) {
+        self.indexes.clear();
+        for (i, p) in self.projects.iter().enumerate() {
+            self.indexes.add(i, p);
+        }
+    }
+
+    fn by_positions(&self, positions: Option<&Vec<usize>>) -> Vec<&Project> {
+        match positions {
+            Some(positions) => positions.iter().map(|i| &self.projects[*i]).collect(),
+            None => vec![],
+        }
+    }
+
+    fn scan<F: Fn(&Project) -> bool>(&self, f: F) -> Vec<&Project> {
+        self.projects.iter().filter(|p| f(p)).collect()
     }
 
+    // find_by_id returns projects with id, uses index if exists
+    pub fn find_by_id(&self, id: &str) -> Vec<&Project> {
+        match self.indexes.id {
+            Some(ref idx) => self.by_positions(idx.get(id)),
+            None => self.scan(|p| p.id == id),
+        }
+    }
+
+    // find_by_category returns projects of category, uses index if exists
+    pub fn find_by_category(&self, category: &str) -> Vec<&Project> {
+        match self.indexes.category {
+            Some(ref idx) => self.by_positions(idx.get(category)),
+            None => self.scan(|p| p.category == category),
+        }
+    }
+
+    // find_by_responsible returns projects of responsible, uses index if exists
+    pub fn find_by_responsible(&self, responsible: &str) -> Vec<&Project> {
+        match self.indexes.responsible {
+            Some(ref idx) => self.by_positions(idx.get(responsible)),
+            None => self.scan(|p| p.responsible == responsible),
+        }
+    }
+
+    // find_by_start_date returns projects with start date in range
+    // ordered by start date, uses index if exists
+    pub fn find_by_start_date<R: RangeBounds<NaiveDateTime>>(&self, range: R) -> Vec<&Project> {
+        if is_empty_range(range.start_bound(), range.end_bound()) {
+            return vec![];
+        }
+        match self.indexes.start_date {
+            Some(ref idx) => idx
+                .range(range)
+                .flat_map(|(_, positions)| positions.iter().map(|i| &self.projects[*i]))
+                .collect(),
+            None => {
+                let mut found = self.scan(|p| range.contains(&p.start_date));
+                found.sort_by_key(|p| p.start_date);
+                found
+            }
+        }
+    }
+
+    // find_by_savings_amount returns projects with savings amount in range
+    // ordered by amount, projects without amount never match,
+    // uses index if exists
+    pub fn find_by_savings_amount<R: RangeBounds<f64>>(&self, range: R) -> Vec<&Project> {
+        let bounds = (
+            amount_bound(range.start_bound()),
+            amount_bound(range.end_bound()),
+        );
+        if is_empty_range(bounds.0.as_ref(), bounds.1.as_ref()) {
+            return vec![];
+        }
+        match self.indexes.savings_amount {
+            Some(ref idx) => idx
+                .range(bounds)
+                .flat_map(|(_, positions)| positions.iter().map(|i| &self.projects[*i]))
+                .collect(),
+            None => {
+                let mut found = self.scan(|p| match p.savings_amount {
+                    Some(ref v) => range.contains(&v.value()),
+                    None => false,
+                });
+                found.sort_by_key(|p| AmountKey(p.savings_amount.as_ref().unwrap().value()));
+                found
+            }
+        }
+    }
+
+    // insert parses record of values of columns and stores project
     pub fn insert(&mut self, record: Vec<&str>) -> Result<(), String> {
-        let record = COLUMN_NAMES
+        let fields = COLUMN_NAMES
             .iter()
-            .map(|c| {
-                let id = self.columns.get(c as &str).unwrap();
-                record[*id]
+            .map(|c| match self.columns.get(c as &str) {
+                Some(id) => record
+                    .get(*id)
+                    .copied()
+                    .ok_or_else(|| "wrong number of columns".to_string()),
+                None => Err(format!("column {} not found in header", c)),
             })
-            .collect();
+            .collect::<Result<Vec<&str>, String>>()?;
 
-        let p = Project::new(record)?;
+        let mut p = Project::new(fields)?;
+        for (c, id) in self.columns.iter() {
+            if column_type(c).is_some() {
+                continue;
+            }
+            let raw = record.get(*id).ok_or("wrong number of columns")?;
+            let t = self.types.get(c).cloned().unwrap_or(Type::Str);
+            let v = Value::parse(t, raw.trim()).map_err(|err| format!("column {}: {}", c, err))?;
+            p.extra.insert(c.clone(), v);
+            p.keep_raw(c, raw);
+        }
+        self.add(p);
+        Ok(())
+    }
 
+    // add stores project if it passes filters
+    pub fn add(&mut self, p: Project) {
         for f in self.filters.iter() {
             if !(f)(&p) {
-                return Ok(());
+                return;
             }
         }
+        self.indexes.add(self.projects.len(), &p);
         self.projects.push(p);
-        Ok(())
     }
 
-    pub fn iter(&self) -> Iter<Project> {
+    // update sets column to value in all projects matching filter,
+    // returns number of updated projects, storage is not changed on error
+    pub fn update<F: Fn(&Project) -> bool>(
+        &mut self,
+        filter: F,
+        column: &str,
+        value: &str,
+    ) -> Result<usize, String> {
+        self.update_all(filter, &[(column.to_string(), value.to_string())])
+    }
+
+    // update_all sets columns to values of assignments in all projects
+    // matching filter before any of them is set, returns number of updated
+    // projects, storage is not changed on error
+    pub fn update_all<F: Fn(&Project) -> bool>(
+        &mut self,
+        filter: F,
+        assignments: &[(String, String)],
+    ) -> Result<usize, String> {
+        let mut updated = Vec::new();
+        for (i, p) in self.projects.iter().enumerate() {
+            if filter(p) {
+                let mut p = p.clone();
+                for (column, value) in assignments.iter() {
+                    match self.column_type(column) {
+                        Some(t) if column_type(column).is_none() => {
+                            p.set_extra(column, t, value)?
+                        }
+                        _ => p.set(column, value)?,
+                    }
+                }
+                updated.push((i, p));
+            }
+        }
+
+        let n = updated.len();
+        for (i, p) in updated {
+            self.projects[i] = p;
+        }
+        self.reindex();
+        Ok(n)
+    }
+
+    // delete removes all projects matching filter,
+    // returns number of removed projects
+    pub fn delete<F: Fn(&Project) -> bool>(&mut self, filter: F) -> usize {
+        let n = self.projects.len();
+        let mut removed = 0;
+        let mut row = 0;
+        let mut annotations = self.annotations.iter_mut().peekable();
+        self.projects.retain(|p| {
+            // shift annotations preceding kept projects
+            while let Some(a) = annotations.next_if(|a| a.row.is_none_or(|r| r <= row)) {
+                a.row = a.row.map(|r| r - removed);
+            }
+            row += 1;
+            if filter(p) {
+                removed += 1;
+                return false;
+            }
+            true
+        });
+        for a in annotations {
+            a.row = a.row.map(|r| r - removed);
+        }
+        self.reindex();
+        n - self.projects.len()
+    }
+
+    // annotate keeps comment or blank line at position row
+    pub fn annotate(&mut self, line: &str, row: Option<usize>) {
+        self.annotations.push(Annotation {
+            line: line.to_string(),
+            row,
+        });
+    }
+
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    // is_reordered reports whether projects were sorted after insert,
+    // so annotation positions are no longer meaningful
+    pub fn is_reordered(&self) -> bool {
+        self.reordered
+    }
+
+    pub fn iter(&self) -> Iter<'_, Project> {
         self.projects.iter()
     }
 
@@ -70,8 +430,13 @@ impl Storage {
 
     pub fn clear(&mut self) {
         self.columns.clear();
+        self.types.clear();
         self.projects.clear();
         self.filters.clear();
+        self.indexes.clear();
+        self.annotations.clear();
+        self.layout = Layout::default();
+        self.reordered = false;
     }
 }
 
@@ -116,4 +481,244 @@ mod tests {
         assert_eq!(storage.projects.len(), 1);
         assert_eq!(storage.projects[0].id, "2");
     }
+
+    #[test]
+    fn test_find_with_and_without_index() {
+        use chrono::NaiveDate;
+        use std::ops::Bound;
+
+        let mut plain: Storage = Storage::new(vec![Box::new(|_p| true)]);
+        let mut indexed: Storage = Storage::new(vec![Box::new(|_p| true)]);
+        indexed.create_index(IndexKind::Id);
+        indexed.create_index(IndexKind::Category);
+        for s in [&mut plain, &mut indexed].iter_mut() {
+            s.insert(get_record(1)).unwrap();
+            s.insert(get_record(2)).unwrap();
+        }
+        // index created after inserts is built from stored projects
+        indexed.create_index(IndexKind::Responsible);
+        indexed.create_index(IndexKind::StartDate);
+        indexed.create_index(IndexKind::SavingsAmount);
+
+        fn ids(found: Vec<&Project>) -> Vec<&str> {
+            found.iter().map(|p| p.id.as_ref()).collect()
+        }
+        let date = NaiveDate::from_ymd_opt(2013, 6, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+
+        for s in [&plain, &indexed].iter() {
+            assert_eq!(ids(s.find_by_id("2")), vec!["2"]);
+            assert!(s.find_by_id("3").is_empty());
+            assert_eq!(ids(s.find_by_category("Dairy")), vec!["1", "2"]);
+            assert_eq!(ids(s.find_by_responsible("Daisy Milks")), vec!["1", "2"]);
+            assert_eq!(ids(s.find_by_start_date(..)), vec!["2", "1"]);
+            assert_eq!(ids(s.find_by_start_date(date..)), vec!["1"]);
+            assert_eq!(ids(s.find_by_savings_amount(100000.0..)), vec!["2"]);
+            assert!(s.find_by_savings_amount(..100000.0).is_empty());
+            // reversed and empty ranges find nothing
+            let later = date + chrono::Duration::days(1);
+            assert!(s.find_by_start_date(later..date).is_empty());
+            assert!(s.find_by_start_date(date..date).is_empty());
+            assert!(s.find_by_savings_amount(200000.0..=100000.0).is_empty());
+            assert!(s
+                .find_by_savings_amount((Bound::Excluded(5.0), Bound::Excluded(5.0)))
+                .is_empty());
+        }
+
+        indexed.sort_by_start_date();
+        assert_eq!(ids(indexed.find_by_id("2")), vec!["2"]);
+        assert_eq!(ids(indexed.find_by_category("Dairy")), vec!["2", "1"]);
+    }
+
+    #[test]
+    fn test_update() {
+        let mut storage: Storage = Storage::new(vec![Box::new(|_p| true)]);
+        storage.create_index(IndexKind::Category);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+
+        assert_eq!(
+            storage.update(|p| p.id == "1", "Category", "Meat").unwrap(),
+            1
+        );
+        assert_eq!(storage.projects[0].category, "Meat");
+        assert_eq!(storage.find_by_category("Meat").len(), 1);
+        assert_eq!(storage.find_by_category("Dairy").len(), 1);
+
+        // invalid value leaves storage untouched
+        assert!(storage.update(|_p| true, "Complexity", "Easy").is_err());
+        assert!(storage.update(|_p| true, "Unknown", "1").is_err());
+        assert_eq!(storage.projects[0], {
+            let mut p = get_project(1);
+            p.category = String::from("Meat");
+            p
+        });
+    }
+
+    #[test]
+    fn test_update_all() {
+        let mut storage: Storage = Storage::new(vec![Box::new(|_p| true)]);
+        storage.create_index(IndexKind::Category);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+
+        // first assignment changes filtered column, second still applies
+        let assignments = [
+            ("Category".to_string(), "Milk".to_string()),
+            ("Currency".to_string(), "EUR".to_string()),
+        ];
+        assert_eq!(
+            storage
+                .update_all(|p| p.category == "Dairy", &assignments)
+                .unwrap(),
+            2
+        );
+        for p in storage.projects.iter() {
+            assert_eq!(p.category, "Milk");
+            assert_eq!(p.get("Currency").unwrap(), "EUR");
+        }
+        assert_eq!(storage.find_by_category("Milk").len(), 2);
+
+        let invalid = [
+            ("Category".to_string(), "Meat".to_string()),
+            ("Complexity".to_string(), "Easy".to_string()),
+        ];
+        assert!(storage.update_all(|_p| true, &invalid).is_err());
+        assert_eq!(storage.find_by_category("Milk").len(), 2);
+    }
+
+    #[test]
+    fn test_update_typed_extra() {
+        let mut storage: Storage = Storage::new(vec![]);
+        storage.add_column("Year", Type::Number).unwrap();
+        let mut p = get_project(1);
+        p.extra.insert("Year".to_string(), Value::Null);
+        storage.add(p);
+
+        // missing value is parsed by type of column
+        storage.update(|_p| true, "Year", "2015").unwrap();
+        assert_eq!(
+            storage.projects[0].value_of("Year"),
+            Some(Value::Number(2015.0))
+        );
+        storage.update(|_p| true, "Year", "NULL").unwrap();
+        assert!(storage.update(|_p| true, "Year", "next").is_err());
+        assert_eq!(storage.projects[0].value_of("Year"), Some(Value::Null));
+        assert!(storage.update(|_p| true, "Unknown", "1").is_err());
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut storage: Storage = Storage::new(vec![Box::new(|_p| true)]);
+        storage.create_index(IndexKind::Id);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+
+        assert_eq!(storage.delete(|p| p.id == "1"), 1);
+        assert_eq!(storage.delete(|p| p.id == "1"), 0);
+        assert_eq!(storage.size(), 1);
+        assert!(storage.find_by_id("1").is_empty());
+        assert_eq!(storage.find_by_id("2")[0].id, "2");
+    }
+
+    #[test]
+    fn test_delete_shifts_annotations() {
+        let mut storage: Storage = Storage::new(vec![Box::new(|_p| true)]);
+        storage.annotate("# header", None);
+        storage.annotate("# before 1", Some(0));
+        storage.insert(get_record(1)).unwrap();
+        storage.annotate("# before 2", Some(1));
+        storage.insert(get_record(2)).unwrap();
+        storage.annotate("# end", Some(2));
+
+        storage.delete(|p| p.id == "1");
+        assert_eq!(
+            storage
+                .annotations()
+                .iter()
+                .map(|a| a.row)
+                .collect::<Vec<Option<usize>>>(),
+            vec![None, Some(0), Some(0), Some(1)]
+        );
+        assert!(!storage.is_reordered());
+    }
+
+    #[test]
+    fn test_sort_by_column_and_group_by() {
+        let mut storage: Storage = Storage::new(vec![Box::new(|_p| true)]);
+        storage.insert(get_record(2)).unwrap();
+        storage.insert(get_record(1)).unwrap();
+        let mut p = get_record(2);
+        p[0] = "3";
+        p[3] = "Office supplies";
+        p[5] = "10";
+        storage.insert(p).unwrap();
+
+        fn ids(s: &Storage) -> Vec<&str> {
+            s.iter().map(|p| p.id.as_ref()).collect()
+        }
+        storage.sort_by_column("Savings amount").unwrap();
+        assert_eq!(ids(&storage), vec!["1", "3", "2"]);
+        storage.sort_by_column("Complexity").unwrap();
+        assert_eq!(ids(&storage), vec!["1", "3", "2"]);
+        storage.sort_by_column("Project").unwrap();
+        assert_eq!(ids(&storage), vec!["1", "2", "3"]);
+        assert!(storage.sort_by_column("Unknown").is_err());
+        assert!(storage.is_reordered());
+
+        let groups = storage.group_by("Category").unwrap();
+        assert_eq!(
+            groups
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.len()))
+                .collect::<Vec<(&str, usize)>>(),
+            vec![("Dairy", 2), ("Office supplies", 1)]
+        );
+        assert!(storage.group_by("Unknown").is_err());
+    }
+
+    #[test]
+    fn test_derive() {
+        use chrono::NaiveDate;
+
+        let mut storage: Storage = Storage::new(vec![Box::new(|_p| true)]);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+        let now = NaiveDate::from_ymd_opt(2014, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+
+        let d = Derived::parse("Savings kEUR = Savings amount / 1000").unwrap();
+        storage.derive(&d, now).unwrap();
+        let d = Derived::parse("Age days = today - Start date").unwrap();
+        storage.derive(&d, now).unwrap();
+        // derived columns can be used by other derived columns
+        let d = Derived::parse("Age weeks = round(Age days / 7)").unwrap();
+        storage.derive(&d, now).unwrap();
+
+        assert_eq!(storage.column_type("Age days"), Some(Type::Number));
+        assert_eq!(
+            storage.get_columns()[8..].to_vec(),
+            vec!["Savings kEUR", "Age days", "Age weeks"]
+        );
+        let p = storage.find_by_id("2")[0];
+        assert_eq!(p.get("Savings kEUR").unwrap(), "141.415942696");
+        assert_eq!(p.get("Age days").unwrap(), "365");
+        assert_eq!(p.get("Age weeks").unwrap(), "52");
+
+        storage.sort_by_column("Savings kEUR").unwrap();
+        assert_eq!(storage.iter().next().unwrap().id, "1");
+        storage.sort_by_column("Age days").unwrap();
+        assert_eq!(storage.iter().next().unwrap().id, "1");
+
+        assert!(storage
+            .derive(&Derived::parse("Bad = year(Category)").unwrap(), now)
+            .is_err());
+        assert!(storage
+            .derive(&Derived::parse("Age days = 1").unwrap(), now)
+            .is_err());
+    }
 }
@@ -0,0 +1,99 @@
+use super::Storage;
+use project::Project;
+use std::cell::Cell;
+
+// Page is a window of projects after filtering and sorting,
+// projects from offset on, at most limit of them if set
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Page {
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl Page {
+    pub fn new(offset: usize, limit: Option<usize>) -> Page {
+        Page { offset, limit }
+    }
+
+    // number returns page n of pages of size projects, numbered from 1
+    pub fn number(n: usize, size: usize) -> Result<Page, String> {
+        if n == 0 {
+            return Err("pages are numbered from 1".to_string());
+        }
+        match (n - 1).checked_mul(size) {
+            Some(offset) => Ok(Page::new(offset, Some(size))),
+            None => Err(format!("page {} is out of range", n)),
+        }
+    }
+
+    // range returns start and end positions of page in len projects
+    pub fn range(&self, len: usize) -> (usize, usize) {
+        let start = self.offset.min(len);
+        let end = match self.limit {
+            Some(limit) => start.saturating_add(limit).min(len),
+            None => len,
+        };
+        (start, end)
+    }
+
+    // slice returns items of page
+    pub fn slice<'a, T>(&self, items: &'a [T]) -> &'a [T] {
+        let (start, end) = self.range(items.len());
+        &items[start..end]
+    }
+}
+
+impl Storage {
+    // view returns projects of page in storage order
+    pub fn view(&self, page: Page) -> &[Project] {
+        page.slice(&self.projects)
+    }
+
+    // paginate keeps only projects of page,
+    // returns number of removed projects
+    pub fn paginate(&mut self, page: Page) -> usize {
+        let (start, end) = page.range(self.projects.len());
+        let row = Cell::new(0);
+        self.delete(|_| {
+            let i = row.get();
+            row.set(i + 1);
+            i < start || i >= end
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::*;
+
+    #[test]
+    fn test_page() {
+        let mut storage = Storage::new(vec![]);
+        for id in 1..6 {
+            let mut p = get_project(1);
+            p.id = id.to_string();
+            storage.add(p);
+        }
+        let ids = |projects: &[Project]| -> Vec<String> {
+            projects.iter().map(|p| p.id.clone()).collect()
+        };
+
+        assert_eq!(
+            ids(storage.view(Page::default())),
+            ["1", "2", "3", "4", "5"]
+        );
+        assert_eq!(ids(storage.view(Page::new(1, Some(2)))), ["2", "3"]);
+        assert_eq!(ids(storage.view(Page::new(3, None))), ["4", "5"]);
+        assert!(storage.view(Page::new(7, Some(2))).is_empty());
+        assert_eq!(ids(storage.view(Page::number(3, 2).unwrap())), ["5"]);
+        assert!(Page::number(0, 2).is_err());
+        assert!(Page::number(usize::MAX, 2).is_err());
+        assert_eq!(Page::new(1, Some(usize::MAX)).range(5), (1, 5));
+
+        assert_eq!(storage.paginate(Page::number(2, 2).unwrap()), 3);
+        assert_eq!(ids(storage.view(Page::default())), ["3", "4"]);
+        assert_eq!(storage.find_by_id("4").len(), 1);
+        assert!(storage.find_by_id("5").is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+use super::Storage;
+use project::{Project, Type, Value};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+pub const RANK_COLUMN: &str = "Rank";
+
+// Ranking decides ranks of projects with equal values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ranking {
+    // equal values share rank and following ranks are skipped, 1 2 2 4
+    Standard,
+    // equal values share rank and following ranks are not skipped, 1 2 2 3
+    Dense,
+}
+
+impl Storage {
+    // top ranks projects by numeric column in descending order within
+    // groups of column `by`, adds rank column and keeps projects ranked
+    // n or better ordered by group and rank. Savings amounts are ranked
+    // per currency, so amounts of different currencies are not compared.
+    // Projects without value are removed.
+    pub fn top(
+        &mut self,
+        n: Option<usize>,
+        column: &str,
+        by: Option<&str>,
+        ranking: Ranking,
+    ) -> Result<(), String> {
+        match self.column_type(column) {
+            Some(Type::Number) => {}
+            Some(t) => return Err(format!("column {} of type {} can not be ranked", column, t)),
+            None => return Err(format!("unknown column {}", column)),
+        }
+        if let Some(by) = by {
+            if !self.columns.contains_key(by) {
+                return Err(format!("unknown column {}", by));
+            }
+        }
+        self.add_column(RANK_COLUMN, Type::Number)?;
+
+        let group = |p: &Project| -> Vec<Value> {
+            let mut key = vec![];
+            if let Some(by) = by {
+                key.push(p.value_of(by).unwrap_or(Value::Null));
+            }
+            if column == "Savings amount" {
+                key.push(p.value_of("Currency").unwrap_or(Value::Null));
+            }
+            key
+        };
+        let value = |p: &Project| match p.value_of(column) {
+            Some(Value::Number(v)) => Some(v),
+            _ => None,
+        };
+        // NaN values are ranked last and share their rank
+        let descending = |a: f64, b: f64| match (a.is_nan(), b.is_nan()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => b.partial_cmp(&a).unwrap(),
+        };
+
+        self.delete(|p| value(p).is_none());
+        // stable sort keeps storage order of equal projects
+        self.projects.sort_by(|a, b| {
+            group(a)
+                .cmp(&group(b))
+                .then_with(|| descending(value(a).unwrap(), value(b).unwrap()))
+        });
+
+        let mut last: BTreeMap<Vec<Value>, (usize, usize, f64)> = BTreeMap::new();
+        for p in self.projects.iter_mut() {
+            let v = value(p).unwrap_or_default();
+            // position, rank and value of previous project of group
+            let (pos, rank) = match last.get(&group(p)) {
+                Some(&(pos, rank, prev)) if descending(prev, v) == Ordering::Equal => {
+                    (pos + 1, rank)
+                }
+                Some(&(pos, rank, _)) => match ranking {
+                    Ranking::Standard => (pos + 1, pos + 1),
+                    Ranking::Dense => (pos + 1, rank + 1),
+                },
+                None => (1, 1),
+            };
+            last.insert(group(p), (pos, rank, v));
+            p.extra
+                .insert(RANK_COLUMN.to_string(), Value::Number(rank as f64));
+        }
+        self.reordered = true;
+
+        if let Some(n) = n {
+            self.delete(|p| p.value_of(RANK_COLUMN) > Some(Value::Number(n as f64)));
+        } else {
+            self.reindex();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::*;
+
+    fn storage(amounts: &[(&'static str, &'static str, &'static str)]) -> Storage {
+        let ids: Vec<String> = (1..=amounts.len()).map(|i| i.to_string()).collect();
+        let records = amounts
+            .iter()
+            .zip(ids.iter())
+            .map(|((category, amount, currency), id)| {
+                let mut p = get_record(2);
+                p[0] = id;
+                p[3] = category;
+                p[5] = amount;
+                p[6] = currency;
+                p
+            })
+            .collect();
+        get_storage(records)
+    }
+
+    fn ranks(storage: &Storage) -> Vec<(String, String)> {
+        storage
+            .iter()
+            .map(|p| (p.id.clone(), p.get(RANK_COLUMN).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_top() {
+        let amounts = [
+            ("Dairy", "10", "EUR"),
+            ("Dairy", "30", "EUR"),
+            ("Meat", "20", "EUR"),
+            ("Dairy", "30", "EUR"),
+            ("Dairy", "NULL", "NULL"),
+            ("Meat", "5", "EUR"),
+            ("Dairy", "20", "EUR"),
+        ];
+        let pairs = |v: Vec<(&str, &str)>| -> Vec<(String, String)> {
+            v.iter()
+                .map(|(a, b)| (a.to_string(), b.to_string()))
+                .collect()
+        };
+
+        let mut s = storage(&amounts);
+        s.top(None, "Savings amount", None, Ranking::Standard)
+            .unwrap();
+        assert_eq!(
+            ranks(&s),
+            pairs(vec![
+                ("2", "1"),
+                ("4", "1"),
+                ("3", "3"),
+                ("7", "3"),
+                ("1", "5"),
+                ("6", "6")
+            ])
+        );
+
+        let mut s = storage(&amounts);
+        s.top(Some(2), "Savings amount", Some("Category"), Ranking::Dense)
+            .unwrap();
+        assert_eq!(
+            ranks(&s),
+            pairs(vec![
+                ("2", "1"),
+                ("4", "1"),
+                ("7", "2"),
+                ("3", "1"),
+                ("6", "2")
+            ])
+        );
+        assert_eq!(s.column_type(RANK_COLUMN), Some(Type::Number));
+        assert!(s.top(None, "Savings amount", None, Ranking::Dense).is_err());
+
+        // NaN is not greater or less than numbers, it is ranked last
+        let mut s = storage(&[
+            ("Dairy", "NaN", "EUR"),
+            ("Dairy", "10", "EUR"),
+            ("Dairy", "NaN", "EUR"),
+            ("Dairy", "-5", "EUR"),
+            ("Dairy", "20", "EUR"),
+        ]);
+        s.top(None, "Savings amount", None, Ranking::Standard)
+            .unwrap();
+        assert_eq!(
+            ranks(&s),
+            pairs(vec![
+                ("5", "1"),
+                ("2", "2"),
+                ("4", "3"),
+                ("1", "4"),
+                ("3", "4")
+            ])
+        );
+
+        let mut s = storage(&amounts);
+        assert!(s.top(None, "Category", None, Ranking::Dense).is_err());
+        assert!(s
+            .top(None, "Savings amount", Some("Unknown"), Ranking::Dense)
+            .is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use super::Storage;
+use project::{Project, Type, Value};
+use search::Search;
+
+pub const RELEVANCE_COLUMN: &str = "Relevance";
+
+impl Storage {
+    // rank_relevance adds relevance column of projects by search and orders
+    // projects by it in descending order, order of equal projects is kept
+    pub fn rank_relevance(&mut self, search: &Search) -> Result<(), String> {
+        self.add_column(RELEVANCE_COLUMN, Type::Number)?;
+        for p in self.projects.iter_mut() {
+            let score = search.score(p);
+            p.extra
+                .insert(RELEVANCE_COLUMN.to_string(), Value::Number(score));
+        }
+        let score = |p: &Project| match p.extra.get(RELEVANCE_COLUMN) {
+            Some(Value::Number(n)) => *n,
+            _ => 0.0,
+        };
+        self.projects.sort_by(|a, b| score(b).total_cmp(&score(a)));
+        self.reordered = true;
+        self.reindex();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::*;
+
+    #[test]
+    fn test_rank_relevance() {
+        let mut storage = Storage::new(vec![]);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+
+        storage
+            .rank_relevance(&Search::parse("milk").unwrap())
+            .unwrap();
+        let ranked: Vec<(String, Option<String>)> = storage
+            .iter()
+            .map(|p| (p.id.clone(), p.get(RELEVANCE_COLUMN)))
+            .collect();
+        assert_eq!(
+            ranked,
+            [
+                ("2".to_string(), Some("3".to_string())),
+                ("1".to_string(), Some("2".to_string())),
+            ]
+        );
+        assert_eq!(storage.column_type(RELEVANCE_COLUMN), Some(Type::Number));
+        assert_eq!(storage.find_by_id("1").len(), 1);
+    }
+}
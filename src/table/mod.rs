@@ -0,0 +1,171 @@
+use json;
+use project::Project;
+use std::io::{self, Write};
+
+const SEP: &str = "\t";
+
+// Table is a report of rows of printed values under header columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(columns: Vec<String>) -> Table {
+        Table {
+            columns,
+            rows: vec![],
+        }
+    }
+
+    // from_projects returns table of projects values of columns
+    // as printed in output
+    pub fn from_projects<'a, I>(projects: I, columns: &[&str]) -> Table
+    where
+        I: IntoIterator<Item = &'a Project>,
+    {
+        let mut table = Table::new(columns.iter().map(|c| c.to_string()).collect());
+        table.rows = projects
+            .into_iter()
+            .map(|p| {
+                columns
+                    .iter()
+                    .map(|c| p.get(c).unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+        table
+    }
+
+    // highlight replaces values of rows by highlighted ones
+    // returned by f for column and value
+    pub fn highlight<F: Fn(&str, &str) -> String>(&mut self, f: F) {
+        for row in self.rows.iter_mut() {
+            for (c, v) in self.columns.iter().zip(row.iter_mut()) {
+                *v = f(c, v);
+            }
+        }
+    }
+
+    // write_tsv writes header and rows in tsv format
+    pub fn write_tsv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(self.columns.join(SEP).as_bytes())?;
+        writer.write_all(b"\n")?;
+        for row in self.rows.iter() {
+            writer.write_all(row.join(SEP).as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()
+    }
+
+    // write_text writes header and rows as aligned text table
+    // for reading in terminal
+    pub fn write_text<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut widths: Vec<usize> = self.columns.iter().map(|c| c.chars().count()).collect();
+        for row in self.rows.iter() {
+            for (w, v) in widths.iter_mut().zip(row.iter()) {
+                *w = (*w).max(v.chars().count());
+            }
+        }
+
+        let line = |values: &[String]| -> String {
+            let cells: Vec<String> = values
+                .iter()
+                .zip(widths.iter())
+                .map(|(v, w)| format!("{:<1$}", v, w))
+                .collect();
+            format!("{}\n", cells.join("  ").trim_end())
+        };
+        writer.write_all(line(&self.columns).as_bytes())?;
+        let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        writer.write_all(line(&rule).as_bytes())?;
+        for row in self.rows.iter() {
+            writer.write_all(line(row).as_bytes())?;
+        }
+        writer.flush()
+    }
+
+    // write_markdown writes header and rows as markdown table,
+    // pipes and line breaks of values are escaped
+    pub fn write_markdown<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let line = |values: &[String]| -> String {
+            let cells: Vec<String> = values
+                .iter()
+                .map(|v| v.replace('|', "\\|").replace('\n', "<br>"))
+                .collect();
+            format!("| {} |\n", cells.join(" | "))
+        };
+        writer.write_all(line(&self.columns).as_bytes())?;
+        let rule: Vec<String> = self.columns.iter().map(|_| "---".to_string()).collect();
+        writer.write_all(line(&rule).as_bytes())?;
+        for row in self.rows.iter() {
+            writer.write_all(line(row).as_bytes())?;
+        }
+        writer.flush()
+    }
+
+    // write_json writes rows as json array of objects by column,
+    // one row per line
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(b"[")?;
+        for (i, row) in self.rows.iter().enumerate() {
+            let fields: Vec<(&str, String)> = self
+                .columns
+                .iter()
+                .zip(row.iter())
+                .map(|(c, v)| (c.as_str(), json::quote(v)))
+                .collect();
+            writer.write_all(if i == 0 { b"\n" } else { b",\n" })?;
+            writer.write_all(json::object(&fields).as_bytes())?;
+        }
+        writer.write_all(b"\n]\n")?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str;
+
+    #[test]
+    fn test_table() {
+        let mut table = Table::new(vec!["Period".to_string(), "Projects".to_string()]);
+        table.rows.push(vec!["2013".to_string(), "2".to_string()]);
+        table.rows.push(vec!["2014".to_string(), "1".to_string()]);
+
+        let mut buf: Vec<u8> = Vec::new();
+        table.write_tsv(&mut buf).unwrap();
+        assert_eq!(
+            str::from_utf8(&buf).unwrap(),
+            "Period\tProjects\n2013\t2\n2014\t1\n"
+        );
+
+        let mut buf: Vec<u8> = Vec::new();
+        table.write_text(&mut buf).unwrap();
+        assert_eq!(
+            str::from_utf8(&buf).unwrap(),
+            "Period  Projects\n------  --------\n2013    2\n2014    1\n"
+        );
+
+        let mut buf: Vec<u8> = Vec::new();
+        table.write_markdown(&mut buf).unwrap();
+        assert_eq!(
+            str::from_utf8(&buf).unwrap(),
+            "| Period | Projects |\n| --- | --- |\n| 2013 | 2 |\n| 2014 | 1 |\n"
+        );
+        table.rows[0][0] = "a|b\nc".to_string();
+        let mut buf: Vec<u8> = Vec::new();
+        table.write_markdown(&mut buf).unwrap();
+        assert!(str::from_utf8(&buf).unwrap().contains("| a\\|b<br>c | 2 |"));
+        table.rows[0][0] = "2013".to_string();
+
+        let mut buf: Vec<u8> = Vec::new();
+        table.write_json(&mut buf).unwrap();
+        assert_eq!(
+            str::from_utf8(&buf).unwrap(),
+            "[\n{\"Period\":\"2013\",\"Projects\":\"2\"},\n{\"Period\":\"2014\",\"Projects\":\"1\"}\n]\n"
+        );
+    }
+}
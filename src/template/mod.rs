@@ -0,0 +1,348 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::NaiveDateTime;
+use project::{Project, Type, Value};
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+use storage::Storage;
+
+// date format of date filter without argument
+const DATE_FORMAT: &str = "%Y-%m-%d";
+// decimals of money filter without argument
+const MONEY_DECIMALS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Section {
+    Header,
+    Row,
+    Footer,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    // strftime format of date
+    Date(String),
+    // number of decimals of amount with thousands separators
+    Money(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    Column(String),
+    // number of projects
+    Count,
+    // sum of numeric column of projects
+    Sum(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Value(Source, Option<Filter>),
+}
+
+// Template is a text of header, row and footer sections, row is repeated
+// for every project, sections start with {{header}}, {{row}} or {{footer}}
+// lines and text before them is row. Placeholders are {Column} in row,
+// {count} and {sum(Column)} in header and footer, and take date or money
+// filters, e.g. {Start date|date:%d.%m.%Y} or {Savings amount|money},
+// {{ and }} print braces
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    header: Vec<Part>,
+    row: Vec<Part>,
+    footer: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(s: &str) -> Result<Template, String> {
+        let mut texts = [String::new(), String::new(), String::new()];
+        let mut section = Section::Row;
+        for line in s.split_inclusive('\n') {
+            section = match line.trim() {
+                "{{header}}" => Section::Header,
+                "{{row}}" => Section::Row,
+                "{{footer}}" => Section::Footer,
+                _ => {
+                    texts[section as usize].push_str(line);
+                    continue;
+                }
+            };
+        }
+        let [header, row, footer] = texts;
+        Ok(Template {
+            header: parse_parts(&header, Section::Header)?,
+            row: parse_parts(&row, Section::Row)?,
+            footer: parse_parts(&footer, Section::Footer)?,
+        })
+    }
+
+    // check checks that columns of placeholders are in storage
+    // and filters match their types
+    pub fn check(&self, storage: &Storage) -> Result<(), String> {
+        for part in self.header.iter().chain(&self.row).chain(&self.footer) {
+            let (column, filter) = match part {
+                Part::Value(Source::Column(c), filter) => (c, filter),
+                Part::Value(Source::Sum(c), filter) => {
+                    if storage.column_type(c) != Some(Type::Number) {
+                        return Err(format!("sum of non numeric column {}", c));
+                    }
+                    (c, filter)
+                }
+                _ => continue,
+            };
+            let t = match storage.column_type(column) {
+                Some(t) => t,
+                None => return Err(format!("unknown column {}", column)),
+            };
+            match (filter, t) {
+                (Some(Filter::Date(_)), Type::Date) | (Some(Filter::Money(_)), Type::Number) => {}
+                (Some(Filter::Date(_)), _) => {
+                    return Err(format!("date filter of non date column {}", column))
+                }
+                (Some(Filter::Money(_)), _) => {
+                    return Err(format!("money filter of non numeric column {}", column))
+                }
+                (None, _) => {}
+            }
+        }
+        Ok(())
+    }
+
+    // write writes header, row of every project and footer
+    pub fn write<W: Write>(&self, mut writer: W, storage: &Storage) -> io::Result<()> {
+        self.check(storage).map_err(io::Error::other)?;
+        let projects: Vec<&Project> = storage.iter().collect();
+        writer.write_all(render(&self.header, None, &projects).as_bytes())?;
+        for p in projects.iter() {
+            writer.write_all(render(&self.row, Some(p), &projects).as_bytes())?;
+        }
+        writer.write_all(render(&self.footer, None, &projects).as_bytes())?;
+        writer.flush()
+    }
+}
+
+// money formats amount with decimals and thousands separators,
+// e.g. 141,415.94
+pub fn money(amount: f64, decimals: usize) -> String {
+    let s = format!("{:.*}", decimals, amount.abs());
+    let (int, fraction) = match s.find('.') {
+        Some(i) => s.split_at(i),
+        None => (s.as_str(), ""),
+    };
+    let mut grouped = String::new();
+    for (i, c) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    let sign = if amount < 0.0 && s.chars().any(|c| c.is_ascii_digit() && c != '0') {
+        "-"
+    } else {
+        ""
+    };
+    format!("{}{}{}", sign, grouped, fraction)
+}
+
+fn parse_parts(s: &str, section: Section) -> Result<Vec<Part>, String> {
+    let mut parts = vec![];
+    let mut text = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err(format!("unclosed {{{} in template", placeholder)),
+                    }
+                }
+                if !text.is_empty() {
+                    parts.push(Part::Text(text.clone()));
+                    text.clear();
+                }
+                parts.push(parse_placeholder(&placeholder, section)?);
+            }
+            '}' => return Err("unmatched } in template, use }} to print it".to_string()),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Ok(parts)
+}
+
+fn parse_placeholder(s: &str, section: Section) -> Result<Part, String> {
+    let (name, filter) = match s.split_once('|') {
+        Some((name, filter)) => (name.trim(), Some(parse_filter(filter.trim())?)),
+        None => (s.trim(), None),
+    };
+    if name.is_empty() {
+        return Err(format!("empty placeholder {{{}}} in template", s));
+    }
+    let source = if section == Section::Row {
+        Source::Column(name.to_string())
+    } else if name == "count" {
+        Source::Count
+    } else if let Some(column) = name.strip_prefix("sum(").and_then(|n| n.strip_suffix(')')) {
+        Source::Sum(column.trim().to_string())
+    } else {
+        return Err(format!(
+            "{{{}}} in header or footer, expected {{count}} or {{sum(column)}}",
+            s
+        ));
+    };
+    if source == Source::Count && filter.is_some() {
+        return Err(format!("filter of {{count}} in template {{{}}}", s));
+    }
+    Ok(Part::Value(source, filter))
+}
+
+fn parse_filter(s: &str) -> Result<Filter, String> {
+    let (name, arg) = match s.split_once(':') {
+        Some((name, arg)) => (name.trim(), Some(arg)),
+        None => (s, None),
+    };
+    match (name, arg) {
+        ("date", None) => Ok(Filter::Date(DATE_FORMAT.to_string())),
+        ("date", Some(format)) => {
+            // time zones, e.g. %z, can not be printed for dates without them
+            let mut s = String::new();
+            if StrftimeItems::new(format).any(|i| i == Item::Error)
+                || write!(s, "{}", NaiveDateTime::default().format(format)).is_err()
+            {
+                return Err(format!("invalid date format {}", format));
+            }
+            Ok(Filter::Date(format.to_string()))
+        }
+        ("money", None) => Ok(Filter::Money(MONEY_DECIMALS)),
+        ("money", Some(decimals)) => match decimals.trim().parse() {
+            Ok(n) => Ok(Filter::Money(n)),
+            Err(_) => Err(format!(
+                "money filter expects number of decimals, got {}",
+                decimals
+            )),
+        },
+        _ => Err(format!("unknown filter {}, expected date or money", name)),
+    }
+}
+
+// render returns text of parts for project of row section
+// or for all projects of header and footer
+fn render(parts: &[Part], project: Option<&Project>, projects: &[&Project]) -> String {
+    let mut s = String::new();
+    for part in parts.iter() {
+        match part {
+            Part::Text(text) => s.push_str(text),
+            Part::Value(Source::Count, _) => s.push_str(&projects.len().to_string()),
+            Part::Value(Source::Column(c), filter) => {
+                let p = project.unwrap();
+                match (filter, p.value_of(c)) {
+                    (_, None) | (_, Some(Value::Null)) => {}
+                    (Some(Filter::Date(format)), Some(Value::Date(d))) => {
+                        s.push_str(&d.format(format).to_string())
+                    }
+                    (Some(Filter::Money(decimals)), Some(Value::Number(n))) => {
+                        s.push_str(&money(n, *decimals))
+                    }
+                    _ => s.push_str(&p.get(c).unwrap_or_default()),
+                }
+            }
+            Part::Value(Source::Sum(c), filter) => {
+                let sum: f64 = projects
+                    .iter()
+                    .filter_map(|p| match p.value_of(c) {
+                        Some(Value::Number(n)) => Some(n),
+                        _ => None,
+                    })
+                    .sum();
+                match filter {
+                    Some(Filter::Money(decimals)) => s.push_str(&money(sum, *decimals)),
+                    _ => s.push_str(&sum.to_string()),
+                }
+            }
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str;
+    use test_util::*;
+
+    #[test]
+    fn test_template() {
+        let mut storage = Storage::new(vec![]);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+
+        let t = Template::parse(
+            "{{header}}\nProjects {{\n{{row}}\nProject {Project} ({Category}) started \
+             {Start date|date:%d.%m.%Y} saves {Savings amount|money} {Currency}\n\
+             {{footer}}\n}} {count} projects save {sum(Savings amount)|money:1}\n",
+        )
+        .unwrap();
+        let mut buf: Vec<u8> = Vec::new();
+        t.write(&mut buf, &storage).unwrap();
+        assert_eq!(
+            str::from_utf8(&buf).unwrap(),
+            "Projects {\n\
+             Project 1 (Dairy) started 01.01.2014 saves  \n\
+             Project 2 (Dairy) started 01.01.2013 saves 141,415.94 EUR\n\
+             } 2 projects save 141,415.9\n"
+        );
+
+        // text without sections is row
+        let t = Template::parse("{Project};").unwrap();
+        let mut buf: Vec<u8> = Vec::new();
+        t.write(&mut buf, &storage).unwrap();
+        assert_eq!(str::from_utf8(&buf).unwrap(), "1;2;");
+
+        assert!(Template::parse("{Project").is_err());
+        assert!(Template::parse("Project}").is_err());
+        assert!(Template::parse("{Project|upper}").is_err());
+        assert!(Template::parse("{Start date|date:%Q}").is_err());
+        for format in ["%Y %z", "%Z", "%:z", "%#z", "%+"] {
+            assert_eq!(
+                Template::parse(&format!("{{Start date|date:{}}}", format)).unwrap_err(),
+                format!("invalid date format {}", format)
+            );
+        }
+        assert!(Template::parse("{{footer}}\n{Project}").is_err());
+        let t = Template::parse("{Category|money}").unwrap();
+        assert_eq!(
+            t.check(&storage).unwrap_err(),
+            "money filter of non numeric column Category"
+        );
+        let t = Template::parse("{{footer}}\n{sum(Category)}").unwrap();
+        assert_eq!(
+            t.check(&storage).unwrap_err(),
+            "sum of non numeric column Category"
+        );
+        assert_eq!(
+            Template::parse("{Unknown}")
+                .unwrap()
+                .check(&storage)
+                .unwrap_err(),
+            "unknown column Unknown"
+        );
+
+        assert_eq!(money(1234567.891, 2), "1,234,567.89");
+        assert_eq!(money(-1234.4, 0), "-1,234");
+        assert_eq!(money(-0.001, 2), "0.00");
+        assert_eq!(money(12.0, 2), "12.00");
+    }
+}
@@ -0,0 +1,126 @@
+use diff;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use storage::Storage;
+
+// interval of polling watched file for changes
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Watcher detects changes of file by modification time and size
+pub struct Watcher {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+}
+
+impl Watcher {
+    pub fn new(path: &Path) -> Watcher {
+        Watcher {
+            path: path.to_path_buf(),
+            stamp: stamp(path),
+        }
+    }
+
+    // changed reports whether file was modified, created or removed
+    // since watcher was created or changed was called
+    pub fn changed(&mut self) -> bool {
+        let stamp = stamp(&self.path);
+        if stamp == self.stamp {
+            return false;
+        }
+        self.stamp = stamp;
+        true
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+// reload replaces storage with loaded one only if it loads without errors,
+// returns description of changed projects matched by id
+pub fn reload<F>(storage: &mut Storage, load: F) -> Result<String, String>
+where
+    F: FnOnce() -> Result<Storage, String>,
+{
+    let new = load()?;
+    let summary = {
+        let d = diff::diff(storage, &new, &["Project"])?;
+        let parts: Vec<String> = [
+            ("added", "added"),
+            ("removed", "removed"),
+            ("changed", "modified"),
+        ]
+        .iter()
+        .map(|&(kind, name)| {
+            let ids: Vec<&str> = d
+                .changes
+                .iter()
+                .filter(|c| c.kind() == kind)
+                .map(|c| c.project().id.as_str())
+                .collect();
+            if ids.is_empty() {
+                format!("0 {}", name)
+            } else {
+                format!("{} {} ({})", ids.len(), name, ids.join(", "))
+            }
+        })
+        .collect();
+        format!("{} projects: {}", new.size(), parts.join(", "))
+    };
+    *storage = new;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+    use test_util::*;
+
+    #[test]
+    fn test_watcher() {
+        let path = env::temp_dir().join(format!("sad_watcher_{}.tsv", process::id()));
+        fs::write(&path, "first\n").unwrap();
+        let mut watcher = Watcher::new(&path);
+        assert!(!watcher.changed());
+
+        fs::write(&path, "second line\n").unwrap();
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+
+        fs::remove_file(&path).unwrap();
+        assert!(watcher.changed());
+    }
+
+    #[test]
+    fn test_reload() {
+        let mut storage = Storage::new(vec![]);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+
+        let summary = reload(&mut storage, || {
+            let mut new = Storage::new(vec![]);
+            let mut p = get_record(2);
+            p[4] = "Clark Kent";
+            new.insert(p).unwrap();
+            let mut p = get_record(2);
+            p[0] = "3";
+            new.insert(p).unwrap();
+            Ok(new)
+        })
+        .unwrap();
+        assert_eq!(
+            summary,
+            "2 projects: 1 added (3), 1 removed (1), 1 modified (2)"
+        );
+        assert_eq!(storage.size(), 2);
+
+        // storage is kept if new one does not load
+        let err = reload(&mut storage, || Err("on line: 2, parse error".to_string()));
+        assert!(err.is_err());
+        assert_eq!(storage.size(), 2);
+    }
+}
@@ -0,0 +1,304 @@
+use project::{Project, Type, Value};
+use rust_xlsxwriter::{Color, Format, FormatBorder, Workbook, Worksheet, XlsxError};
+use std::collections::BTreeMap;
+use std::io::Write;
+use storage::Storage;
+
+pub const PROJECTS_SHEET: &str = "Projects";
+pub const SUMMARY_SHEET: &str = "Summary";
+
+const DATE_FORMAT: &str = "yyyy-mm-dd hh:mm:ss.000";
+const NUMBER_FORMAT: &str = "#,##0.######";
+// widest column in characters, longer values are cut off on screen
+const MAX_WIDTH: f64 = 60.0;
+
+// Summary is count and savings amount total of projects
+// of one group value and currency
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub group: String,
+    pub currency: Option<String>,
+    pub projects: usize,
+    pub total: Option<f64>,
+}
+
+// write_xlsx writes columns of projects as xlsx workbook with typed cells,
+// money is formatted with its currency, header is styled, frozen and
+// filtered, and if summary_by is set second sheet summarizes its groups
+pub fn write_xlsx<W: Write>(
+    mut writer: W,
+    storage: &Storage,
+    columns: &[&str],
+    summary_by: Option<&str>,
+) -> Result<(), String> {
+    let mut workbook = Workbook::new();
+    let mut formats = Formats::new();
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(PROJECTS_SHEET).map_err(error)?;
+    write_projects(sheet, storage, columns, &mut formats).map_err(error)?;
+
+    if let Some(column) = summary_by {
+        let rows = summary(storage, column)?;
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(SUMMARY_SHEET).map_err(error)?;
+        write_summary(sheet, column, &rows, &mut formats).map_err(error)?;
+    }
+
+    let buf = workbook.save_to_buffer().map_err(error)?;
+    writer.write_all(&buf).map_err(|e| e.to_string())
+}
+
+// summary returns count and total savings amount of projects per
+// value of column and currency, ordered by value and currency
+pub fn summary(storage: &Storage, column: &str) -> Result<Vec<Summary>, String> {
+    let mut rows = vec![];
+    for (group, projects) in storage.group_by(column)? {
+        let mut currencies: BTreeMap<Option<String>, (usize, Option<f64>)> = BTreeMap::new();
+        for p in projects {
+            let entry = currencies
+                .entry(p.currency.as_ref().map(|c| c.to_string()))
+                .or_insert((0, None));
+            entry.0 += 1;
+            if let Some(amount) = &p.savings_amount {
+                entry.1 = Some(entry.1.unwrap_or(0.0) + amount.value());
+            }
+        }
+        for (currency, (projects, total)) in currencies {
+            rows.push(Summary {
+                group: group.clone(),
+                currency,
+                projects,
+                total,
+            });
+        }
+    }
+    Ok(rows)
+}
+
+// Formats are cell formats shared by sheets of workbook
+struct Formats {
+    header: Format,
+    date: Format,
+    number: Format,
+    // money formats by currency
+    money: BTreeMap<String, Format>,
+}
+
+impl Formats {
+    fn new() -> Formats {
+        Formats {
+            header: Format::new()
+                .set_bold()
+                .set_font_color(Color::White)
+                .set_background_color(Color::RGB(0x1F4E78))
+                .set_border_bottom(FormatBorder::Thin),
+            date: Format::new().set_num_format(DATE_FORMAT),
+            number: Format::new().set_num_format(NUMBER_FORMAT),
+            money: BTreeMap::new(),
+        }
+    }
+
+    fn money(&mut self, currency: &str) -> &Format {
+        self.money.entry(currency.to_string()).or_insert_with(|| {
+            Format::new().set_num_format(format!("#,##0.00 \"{}\"", currency.replace('"', "")))
+        })
+    }
+}
+
+fn write_projects(
+    sheet: &mut Worksheet,
+    storage: &Storage,
+    columns: &[&str],
+    formats: &mut Formats,
+) -> Result<(), XlsxError> {
+    let mut widths: Vec<usize> = columns.iter().map(|c| c.chars().count()).collect();
+    for (i, c) in columns.iter().enumerate() {
+        sheet.write_string_with_format(0, i as u16, *c, &formats.header)?;
+    }
+    let mut row = 0;
+    for p in storage.iter() {
+        row += 1;
+        for (i, c) in columns.iter().enumerate() {
+            let width = write_value(sheet, row, i as u16, p, c, storage, formats)?;
+            widths[i] = widths[i].max(width);
+        }
+    }
+    finish(sheet, row, &widths)
+}
+
+// write_value writes typed cell of column of project,
+// returns width of value as printed
+fn write_value(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    p: &Project,
+    column: &str,
+    storage: &Storage,
+    formats: &mut Formats,
+) -> Result<usize, XlsxError> {
+    match p.value_of(column).unwrap_or(Value::Null) {
+        Value::Null => return Ok(0),
+        Value::Str(s) => {
+            sheet.write_string(row, col, &s)?;
+            return Ok(s.chars().count());
+        }
+        Value::Date(d) => {
+            sheet.write_datetime_with_format(row, col, d, &formats.date)?;
+        }
+        Value::Number(n) => match (&p.savings_amount, &p.currency) {
+            (Some(_), Some(currency)) if column == "Savings amount" => {
+                sheet.write_number_with_format(
+                    row,
+                    col,
+                    n,
+                    formats.money(&currency.to_string()),
+                )?;
+            }
+            _ => {
+                sheet.write_number_with_format(row, col, n, &formats.number)?;
+            }
+        },
+    }
+    Ok(match storage.column_type(column) {
+        Some(Type::Date) => DATE_FORMAT.len(),
+        _ => p.get(column).map(|s| s.len()).unwrap_or_default(),
+    })
+}
+
+fn write_summary(
+    sheet: &mut Worksheet,
+    column: &str,
+    rows: &[Summary],
+    formats: &mut Formats,
+) -> Result<(), XlsxError> {
+    let header = [column, "Currency", "Projects", "Savings amount"];
+    let mut widths: Vec<usize> = header.iter().map(|c| c.chars().count()).collect();
+    for (i, c) in header.iter().enumerate() {
+        sheet.write_string_with_format(0, i as u16, *c, &formats.header)?;
+    }
+    for (i, s) in rows.iter().enumerate() {
+        let row = i as u32 + 1;
+        sheet.write_string(row, 0, &s.group)?;
+        widths[0] = widths[0].max(s.group.chars().count());
+        if let Some(currency) = &s.currency {
+            sheet.write_string(row, 1, currency)?;
+        }
+        sheet.write_number(row, 2, s.projects as f64)?;
+        if let (Some(total), Some(currency)) = (s.total, &s.currency) {
+            sheet.write_number_with_format(row, 3, total, formats.money(currency))?;
+            widths[3] = widths[3].max(format!("{:.2} {}", total, currency).len());
+        }
+    }
+    finish(sheet, rows.len() as u32, &widths)
+}
+
+// finish freezes and filters header of sheet with rows below it
+// and sizes columns to their widest values
+fn finish(sheet: &mut Worksheet, rows: u32, widths: &[usize]) -> Result<(), XlsxError> {
+    if widths.is_empty() {
+        return Ok(());
+    }
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.autofilter(0, 0, rows, widths.len() as u16 - 1)?;
+    for (i, w) in widths.iter().enumerate() {
+        // room for filter button
+        let width = (*w as f64 + 4.0).min(MAX_WIDTH);
+        sheet.set_column_width(i as u16, width)?;
+    }
+    Ok(())
+}
+
+fn error(err: XlsxError) -> String {
+    err.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read};
+    use test_util::*;
+    use zip::ZipArchive;
+
+    // part returns xml of file at path in xlsx archive
+    fn part(xlsx: &[u8], path: &str) -> String {
+        let mut archive = ZipArchive::new(Cursor::new(xlsx)).unwrap();
+        let mut xml = String::new();
+        archive
+            .by_name(path)
+            .unwrap()
+            .read_to_string(&mut xml)
+            .unwrap();
+        xml
+    }
+
+    #[test]
+    fn test_write_xlsx() {
+        let mut storage = Storage::new(vec![]);
+        storage.insert(get_record(1)).unwrap();
+        storage.insert(get_record(2)).unwrap();
+        let mut p = get_project(2);
+        p.category = "Bakery".to_string();
+        storage.add(p);
+        let columns = storage.get_columns();
+
+        let mut buf = vec![];
+        write_xlsx(&mut buf, &storage, &columns, Some("Category")).unwrap();
+        let sheet = part(&buf, "xl/worksheets/sheet1.xml");
+        // header is frozen and filtered
+        assert!(sheet.contains("<pane ySplit=\"1\" topLeftCell=\"A2\""));
+        assert!(sheet.contains("<autoFilter ref=\"A1:H4\"/>"));
+        // header and text are strings, dates and amounts numbers of styles
+        // 2 and 3 with date and money formats, missing amount is empty
+        assert!(sheet.contains("<c r=\"A1\" s=\"1\" t=\"s\">"));
+        assert!(sheet.contains("<c r=\"D2\" t=\"s\">"));
+        assert!(sheet.contains("<c r=\"C2\" s=\"2\"><v>41640</v></c>"));
+        assert!(sheet.contains("<c r=\"F3\" s=\"3\"><v>141415.942696</v></c>"));
+        assert!(!sheet.contains("<c r=\"F2\""));
+        let styles = part(&buf, "xl/styles.xml");
+        assert!(styles.contains(
+            "<numFmt numFmtId=\"164\" formatCode=\"yyyy-mm-dd hh:mm:ss.000\"/>\
+             <numFmt numFmtId=\"165\" formatCode=\"#,##0.00 &quot;EUR&quot;\"/>"
+        ));
+        assert!(styles.contains(
+            "<xf numFmtId=\"164\" fontId=\"0\" fillId=\"0\" borderId=\"0\" xfId=\"0\" \
+             applyNumberFormat=\"1\"/><xf numFmtId=\"165\""
+        ));
+        let sheet = part(&buf, "xl/worksheets/sheet2.xml");
+        assert!(sheet.contains("<pane ySplit=\"1\""));
+        assert!(sheet.contains("<autoFilter ref=\"A1:D4\"/>"));
+        assert!(
+            sheet.contains("<c r=\"C2\"><v>1</v></c><c r=\"D2\" s=\"3\"><v>141415.942696</v></c>")
+        );
+
+        assert_eq!(
+            write_xlsx(vec![], &storage, &columns, Some("Unknown")).unwrap_err(),
+            "unknown column Unknown"
+        );
+
+        assert_eq!(
+            summary(&storage, "Category").unwrap(),
+            vec![
+                Summary {
+                    group: "Bakery".to_string(),
+                    currency: Some("EUR".to_string()),
+                    projects: 1,
+                    total: Some(141415.942696),
+                },
+                Summary {
+                    group: "Dairy".to_string(),
+                    currency: None,
+                    projects: 1,
+                    total: None,
+                },
+                Summary {
+                    group: "Dairy".to_string(),
+                    currency: Some("EUR".to_string()),
+                    projects: 1,
+                    total: Some(141415.942696),
+                },
+            ]
+        );
+    }
+}